@@ -0,0 +1,113 @@
+use crate::autocompress::{autocompress, AutoCompressOpts};
+use crate::compress::{Engine, MultiCompressedData};
+use crate::compress_file::FileContents;
+use crate::parallel::join;
+
+// Only the first tokens of an input are tried as the number of lines of its answer, as counts
+// usually come before the data they describe
+const MAX_TOKEN: usize = 16;
+
+// Number of lines of an answer whose input has the count as its `token`-th whitespace-separated
+// token, if the token is an integer and the sum is a valid count
+pub(crate) fn line_count(input: &str, token: usize, offset: i128) -> Option<usize> {
+    let count: i128 = input.split_ascii_whitespace().nth(token)?.parse().ok()?;
+    usize::try_from(count.checked_add(offset)?).ok()
+}
+
+// Lines without the final line break, and whether there is one, as in schemas
+fn split_lines(text: &str) -> (Vec<&str>, bool) {
+    match text.strip_suffix('\n') {
+        Some(body) => (body.split('\n').collect(), true),
+        None => (text.split('\n').collect(), false),
+    }
+}
+
+// The first token of the inputs that is at a constant distance from the line counts of all
+// answers, along with the distance
+fn find_count(inputs: &[&String], answers: &[(Vec<&str>, bool)]) -> Option<(usize, i128)> {
+    (0..MAX_TOKEN).find_map(|token| {
+        let mut offsets = inputs.iter().zip(answers).map(|(input, (lines, _))| {
+            let count: i128 = input.split_ascii_whitespace().nth(token)?.parse().ok()?;
+            (lines.len() as i128).checked_sub(count)
+        });
+        let offset = offsets.next()??;
+        offsets
+            .all(|other| other == Some(offset))
+            .then_some((token, offset))
+    })
+}
+
+// Compresses answers as lists of lines whose lengths are known from the inputs. Returns None if
+// some file is binary or no count is found
+fn answer_lines_compressed(
+    inputs: &[&FileContents],
+    answers: &[&FileContents],
+    opts: AutoCompressOpts,
+) -> Option<MultiCompressedData> {
+    fn text<'a>(file: &&'a FileContents) -> Option<&'a String> {
+        match file {
+            FileContents::Text(s) => Some(s),
+            FileContents::Binary(_) => None,
+        }
+    }
+    let inputs: Vec<&String> = inputs.iter().map(text).collect::<Option<_>>()?;
+    let answers: Vec<(Vec<&str>, bool)> = answers
+        .iter()
+        .map(|answer| text(answer).map(|s| split_lines(s)))
+        .collect::<Option<_>>()?;
+    let (token, offset) = find_count(&inputs, &answers)?;
+
+    let lines: Vec<String> = answers
+        .iter()
+        .flat_map(|(lines, _)| lines.iter().map(|line| line.to_string()))
+        .collect();
+    let lines_refs: Vec<&String> = lines.iter().collect();
+    let newlines: Vec<usize> = answers
+        .iter()
+        .map(|(_, newline)| *newline as usize)
+        .collect();
+    let newlines_refs: Vec<&usize> = newlines.iter().collect();
+    let (line, newline) = join(
+        || autocompress(&lines_refs, opts.subproblem()),
+        || autocompress(&newlines_refs, opts.subproblem()),
+    );
+
+    let mut line_data = line.binary_data.into_iter();
+    let binary_data = answers
+        .iter()
+        .zip(newline.binary_data)
+        .map(|((lines, _), mut bits)| {
+            for chunk in line_data.by_ref().take(lines.len()) {
+                bits.extend(&chunk);
+            }
+            bits
+        })
+        .collect();
+
+    Some(MultiCompressedData {
+        engine: Engine::AnswerLines {
+            token,
+            offset,
+            newline: Box::new(newline.engine),
+            line: Box::new(line.engine),
+        },
+        binary_data,
+    })
+}
+
+// Answers are compressed on their own unless knowing the inputs makes them smaller
+pub(crate) fn answers_compressed(
+    inputs: &[&FileContents],
+    answers: &[&FileContents],
+    opts: AutoCompressOpts,
+) -> MultiCompressedData {
+    assert_eq!(inputs.len(), answers.len());
+    let (plain, conditioned) = join(
+        || autocompress(answers, opts),
+        || answer_lines_compressed(inputs, answers, opts),
+    );
+    match conditioned {
+        Some(conditioned) if conditioned.weight() < plain.weight() => conditioned,
+        _ => plain,
+    }
+}
@@ -1,24 +1,217 @@
 use crate::bits::Bits;
 use crate::compress::{Compress, CompressedData, Engine, MultiCompressedData};
+use crate::decompress::Value;
+use crate::explain::{record_choice, Choices};
 use crate::huffman::huffman;
+use crate::parallel::{join, map};
+use crate::range_coding::range_coded;
 use std::collections::HashMap;
+use std::fmt::Debug;
+use std::hash::{Hash, Hasher};
+use std::sync::{Mutex, MutexGuard};
+use std::time::Instant;
 
-#[derive(Clone, Copy, Debug)]
-pub struct AutoCompressOpts {
+/// Strategies that [`autocompress`] is allowed to try.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct AutoCompressOpts<'a> {
+    /// Try deduplicating equal objects and splitting objects into categories. Nested groups, such
+    /// as alphabets, are compressed with this enabled regardless.
     pub enable_dedup_and_categories: bool,
+    /// Try compressing the whole group as a single list. Nested groups, such as alphabets, are
+    /// compressed with this enabled regardless.
     pub enable_stateful: bool,
+    /// Stop trying new strategies after this moment and finish with the ones that are already
+    /// known to work.
+    pub deadline: Option<Instant>,
+    /// Compression level, from 0 to [`AutoCompressOpts::MAX_LEVEL`]. Each level tries the
+    /// strategies of the previous one and adds more:
+    ///
+    /// 0. Direct encodings only.
+    /// 1. Deduplication with canonical Huffman codes, splitting text by separators.
+    /// 2. Run-length encoding, compressing groups as a single list.
+    /// 3. One level of category splitting, range coding.
+    /// 4. Sorted integer sets, context modeling of text.
+    /// 5. Ordered Huffman codes, two levels of category splitting.
+    /// 6. Unlimited category splitting.
+    pub level: u8,
+    /// How many category splits the objects have already gone through.
+    pub category_depth: usize,
+    /// Deduplication is tried if the number of distinct objects is below this percentage of the
+    /// number of objects, plus [`dedup_slack`](Self::dedup_slack), and below the number of
+    /// objects.
+    pub dedup_percent: usize,
+    /// See [`dedup_percent`](Self::dedup_percent).
+    pub dedup_slack: usize,
+    /// Objects are split into categories by a key if the number of categories is below this
+    /// percentage of the number of objects.
+    pub split_percent: usize,
+    /// Run-length encoding of a list is tried if the number of runs is below this percentage of
+    /// its length.
+    pub rle_percent: usize,
+    /// Text is split by a separator if the separator occurs at least this percentage of the
+    /// number of strings times.
+    pub separator_percent: usize,
+    /// State shared by the calls of one compression. [`autocompress`] creates its own if this is
+    /// `None`, so it only needs to be set to share results between calls.
+    pub context: Option<&'a SearchContext>,
 }
 
-impl Default for AutoCompressOpts {
+impl Default for AutoCompressOpts<'_> {
     fn default() -> Self {
         AutoCompressOpts {
             enable_dedup_and_categories: true,
             enable_stateful: true,
+            deadline: None,
+            level: Self::MAX_LEVEL,
+            category_depth: 0,
+            dedup_percent: 50,
+            dedup_slack: 3,
+            split_percent: 50,
+            rle_percent: 50,
+            separator_percent: 100,
+            context: None,
         }
     }
 }
 
-fn try_autocompress_dedup<T: Compress>(objs: &[&T]) -> Option<MultiCompressedData> {
+impl AutoCompressOpts<'_> {
+    pub const MAX_LEVEL: u8 = 6;
+
+    // Options for compressing an unrelated group of objects, e.g. the alphabet of a group. All
+    // strategies are enabled again, but the limits and thresholds stay the same
+    pub(crate) fn subproblem(self) -> Self {
+        Self {
+            enable_dedup_and_categories: true,
+            enable_stateful: true,
+            category_depth: 0,
+            ..self
+        }
+    }
+
+    // The options as a part of a memo key, which outlives the context
+    fn without_context(self) -> AutoCompressOpts<'static> {
+        let AutoCompressOpts {
+            enable_dedup_and_categories,
+            enable_stateful,
+            deadline,
+            level,
+            category_depth,
+            dedup_percent,
+            dedup_slack,
+            split_percent,
+            rle_percent,
+            separator_percent,
+            context: _,
+        } = self;
+        AutoCompressOpts {
+            enable_dedup_and_categories,
+            enable_stateful,
+            deadline,
+            level,
+            category_depth,
+            dedup_percent,
+            dedup_slack,
+            split_percent,
+            rle_percent,
+            separator_percent,
+            context: None,
+        }
+    }
+
+    pub(crate) fn dedup_pays_off(&self, n_values: usize, n_objs: usize) -> bool {
+        n_values < n_objs.min(percent(n_objs, self.dedup_percent) + self.dedup_slack)
+    }
+
+    pub(crate) fn split_pays_off(&self, n_categories: usize, n_objs: usize) -> bool {
+        n_categories < percent(n_objs, self.split_percent)
+    }
+
+    pub(crate) fn rle_pays_off(&self, n_runs: usize, length: usize) -> bool {
+        n_runs < percent(length, self.rle_percent)
+    }
+
+    // Text without the separator is never split, or splitting would never end
+    pub(crate) fn separator_pays_off(&self, n_separators: usize, n_strings: usize) -> bool {
+        n_separators > 0 && n_separators >= percent(n_strings, self.separator_percent)
+    }
+
+    pub(crate) fn expired(&self) -> bool {
+        self.deadline
+            .is_some_and(|deadline| Instant::now() >= deadline)
+    }
+
+    pub(crate) fn dedup(&self) -> bool {
+        self.level >= 1
+    }
+
+    pub(crate) fn separators(&self) -> bool {
+        self.level >= 1
+    }
+
+    pub(crate) fn rle(&self) -> bool {
+        self.level >= 2
+    }
+
+    pub(crate) fn stateful(&self) -> bool {
+        self.level >= 2
+    }
+
+    pub(crate) fn range_coding(&self) -> bool {
+        self.level >= 3
+    }
+
+    pub(crate) fn int_set(&self) -> bool {
+        self.level >= 4
+    }
+
+    pub(crate) fn context_modeling(&self) -> bool {
+        self.level >= 4
+    }
+
+    pub(crate) fn huffman_ordered(&self) -> bool {
+        self.level >= 5
+    }
+
+    pub(crate) fn categories(&self) -> bool {
+        match self.level {
+            0..=2 => false,
+            3..=4 => self.category_depth < 1,
+            5 => self.category_depth < 2,
+            _ => true,
+        }
+    }
+}
+
+fn percent(n: usize, p: usize) -> usize {
+    n.saturating_mul(p) / 100
+}
+
+// Picks the lightest of the candidates, preferring earlier ones on ties. The rest are remembered
+// in the context if it records choices for explain mode
+pub(crate) fn choose(
+    candidates: Vec<(&'static str, MultiCompressedData)>,
+    opts: AutoCompressOpts,
+) -> MultiCompressedData {
+    let weights: Vec<usize> = candidates.iter().map(|(_, data)| data.weight()).collect();
+    let best = (0..candidates.len()).min_by_key(|i| weights[*i]).unwrap();
+    let rejected = candidates
+        .iter()
+        .zip(&weights)
+        .enumerate()
+        .filter(|(i, _)| *i != best)
+        .map(|(_, ((name, _), weight))| (*name, *weight))
+        .collect();
+    let chosen = candidates.into_iter().nth(best).unwrap().1;
+    if let Some(mut choices) = opts.context.and_then(SearchContext::choices) {
+        record_choice(&mut choices, &chosen, rejected);
+    }
+    chosen
+}
+
+fn try_autocompress_dedup<T: Compress>(
+    objs: &[&T],
+    opts: AutoCompressOpts,
+) -> Option<MultiCompressedData> {
     let mut values_list = Vec::new();
     let mut index_of_value = HashMap::new();
     let mut indices = Vec::with_capacity(objs.len());
@@ -34,7 +227,7 @@ fn try_autocompress_dedup<T: Compress>(objs: &[&T]) -> Option<MultiCompressedDat
 
     if objs.len() > 1 && values_list.len() == 1 {
         // Constant
-        let data = autocompress_one(objs[0], AutoCompressOpts::default());
+        let data = autocompress_one(objs[0], opts.subproblem());
         Some(MultiCompressedData {
             engine: Engine::Constant {
                 engine: Box::new(data.engine),
@@ -42,81 +235,138 @@ fn try_autocompress_dedup<T: Compress>(objs: &[&T]) -> Option<MultiCompressedDat
             },
             binary_data: vec![Bits::new(); objs.len()],
         })
-    } else if values_list.len() < objs.len().min(objs.len() / 2 + 3) {
+    } else if opts.dedup_pays_off(values_list.len(), objs.len()) {
         // Reasonable to compress
-
-        // Huffman encoding
-        let huffman_encoded = huffman(objs, AutoCompressOpts::default());
-
-        // Alphabet-based encoding
-        let mut values_compressed = autocompress(&values_list, AutoCompressOpts::default());
-        let indices_refs: Vec<&usize> = indices.iter().collect();
-        let indices_compressed = autocompress(
-            &indices_refs,
-            AutoCompressOpts {
-                enable_dedup_and_categories: false,
-                enable_stateful: true,
+        let ((huffman_encoded, alphabet_encoded), range_encoded) = join(
+            || {
+                join(
+                    // Huffman encoding
+                    || huffman(objs, opts.subproblem()),
+                    // Alphabet-based encoding
+                    || {
+                        if opts.expired() {
+                            return None;
+                        }
+                        let (values_compressed, indices_compressed) = join(
+                            || autocompress_one(&values_list, opts.subproblem()),
+                            || {
+                                let indices_refs: Vec<&usize> = indices.iter().collect();
+                                autocompress(
+                                    &indices_refs,
+                                    AutoCompressOpts {
+                                        enable_dedup_and_categories: false,
+                                        enable_stateful: true,
+                                        ..opts
+                                    },
+                                )
+                            },
+                        );
+                        Some(MultiCompressedData {
+                            engine: Engine::Alphabet {
+                                alphabet_engine: Box::new(values_compressed.engine),
+                                alphabet_data: values_compressed.binary_data,
+                                index: Box::new(indices_compressed.engine),
+                            },
+                            binary_data: indices_compressed.binary_data,
+                        })
+                    },
+                )
             },
-        );
-        let alphabet_encoded = MultiCompressedData {
-            engine: Engine::Alphabet {
-                alphabet_engine: Box::new(values_compressed.engine),
-                alphabet_data: values_compressed.binary_data.pop().unwrap(),
-                index: Box::new(indices_compressed.engine),
+            // Range coding, with fractional bits per object
+            || {
+                if opts.expired() || !opts.range_coding() {
+                    None
+                } else {
+                    range_coded(objs, opts.subproblem())
+                }
             },
-            binary_data: indices_compressed.binary_data,
-        };
+        );
 
-        if huffman_encoded.weight() < alphabet_encoded.weight() {
-            Some(huffman_encoded)
-        } else {
-            Some(alphabet_encoded)
-        }
+        Some(choose(
+            [
+                ("Huffman", Some(huffman_encoded)),
+                ("alphabet", alphabet_encoded),
+                ("range coding", range_encoded),
+            ]
+            .into_iter()
+            .filter_map(|(name, data)| Some((name, data?)))
+            .collect(),
+            opts,
+        ))
     } else {
         None
     }
 }
 
-fn try_autocompress_categories<T: Compress>(objs: &[&T]) -> Option<MultiCompressedData> {
-    let categories = T::split_categories(objs)?;
-    if categories.len() < 2 {
-        return None;
-    }
-
+fn autocompress_categories<T: Compress>(
+    objs: &[&T],
+    categories: Vec<Vec<usize>>,
+    opts: AutoCompressOpts,
+) -> MultiCompressedData {
     let mut category_by_obj = vec![0; objs.len()];
     for (i, category) in categories.iter().enumerate() {
         for j in category {
             category_by_obj[*j] = i;
         }
     }
-    let category_by_obj_refs: Vec<&usize> = category_by_obj.iter().collect();
-    let category_by_obj_compressed = autocompress(
-        &category_by_obj_refs,
-        AutoCompressOpts {
-            enable_dedup_and_categories: false,
-            enable_stateful: true,
+
+    let (category_by_obj_compressed, categories_compressed) = join(
+        || {
+            let category_by_obj_refs: Vec<&usize> = category_by_obj.iter().collect();
+            autocompress(
+                &category_by_obj_refs,
+                AutoCompressOpts {
+                    enable_dedup_and_categories: false,
+                    enable_stateful: true,
+                    ..opts
+                },
+            )
+        },
+        || {
+            map(&categories, |category| {
+                let category_objs: Vec<&T> = category.iter().map(|j| objs[*j]).collect();
+                autocompress(
+                    &category_objs,
+                    AutoCompressOpts {
+                        category_depth: opts.category_depth + 1,
+                        ..opts.subproblem()
+                    },
+                )
+            })
         },
     );
 
+    join_categories(
+        &categories,
+        category_by_obj_compressed,
+        categories_compressed,
+    )
+}
+
+// Builds the category split from the compressed category of each object and the compressed
+// objects of each category
+pub(crate) fn join_categories(
+    categories: &[Vec<usize>],
+    category_by_obj_compressed: MultiCompressedData,
+    categories_compressed: Vec<MultiCompressedData>,
+) -> MultiCompressedData {
     let mut binary_data = category_by_obj_compressed.binary_data;
 
     let mut categories_engines = Vec::with_capacity(categories.len());
-    for category in categories.into_iter() {
-        let category_objs: Vec<&T> = category.iter().map(|j| objs[*j]).collect();
-        let data_compressed = autocompress(&category_objs, AutoCompressOpts::default());
+    for (category, data_compressed) in categories.iter().zip(categories_compressed) {
         categories_engines.push(data_compressed.engine);
         for (i, j) in category.iter().enumerate() {
             binary_data[*j].extend(&data_compressed.binary_data[i]);
         }
     }
 
-    Some(MultiCompressedData {
+    MultiCompressedData {
         engine: Engine::CategorySplit {
             categories: categories_engines,
             category: Box::new(category_by_obj_compressed.engine),
         },
         binary_data,
-    })
+    }
 }
 
 fn autocompress_stateful<T: Compress>(objs: &[&T], opts: AutoCompressOpts) -> MultiCompressedData {
@@ -130,8 +380,174 @@ fn autocompress_stateful<T: Compress>(objs: &[&T], opts: AutoCompressOpts) -> Mu
     }
 }
 
-static mut cc: usize = 0usize;
+// The search compresses the same objects many times over, e.g. when dedup, categories and Huffman
+// all recurse into the same values, so results are cached for the duration of a compression.
+// Objects of different types may have the same values, e.g. characters and integers, but they
+// are compressed differently, so the type is a part of the key.
+#[derive(PartialEq, Eq, Hash)]
+struct MemoKey {
+    type_name: &'static str,
+    objs: Vec<Value>,
+    opts: AutoCompressOpts<'static>,
+}
+
+impl MemoKey {
+    fn new<T: Compress>(objs: &[&T], opts: AutoCompressOpts) -> Self {
+        Self {
+            type_name: std::any::type_name::<T>(),
+            objs: objs.iter().map(|obj| obj.to_value()).collect(),
+            opts: opts.without_context(),
+        }
+    }
+
+    // Approximate memory taken by the key, in bytes
+    fn size(&self) -> usize {
+        self.objs.iter().map(value_size).sum()
+    }
+}
+
+fn value_size(value: &Value) -> usize {
+    size_of::<Value>()
+        + match value {
+            Value::Int(_) => 0,
+            Value::Str(s) => s.len(),
+            Value::Bytes(bytes) => bytes.len(),
+            Value::Vec(items) => items.iter().map(value_size).sum(),
+        }
+}
+
+// Results are only cached while the memory they take stays below this many bytes. Past that,
+// compression goes on without caching new results, so memory use stays bounded on large inputs.
+const MEMO_BUDGET: usize = 64 << 20;
+
+// A cached result. The data of all objects is stored as a whole, as most objects only take a few
+// bits and an allocation each would take much more memory than the data.
+struct MemoEntry {
+    engine: Engine,
+    data: Bits,
+    ends: Vec<u32>,
+}
+
+impl MemoEntry {
+    // None if the data is too long to be cached
+    fn new<'a>(engine: &Engine, binary_data: impl IntoIterator<Item = &'a Bits>) -> Option<Self> {
+        let mut data = Bits::new();
+        let mut ends = Vec::new();
+        for bits in binary_data {
+            data.extend(bits);
+            ends.push(u32::try_from(data.len()).ok()?);
+        }
+        Some(Self {
+            engine: engine.clone(),
+            data,
+            ends,
+        })
+    }
+
+    // Approximate memory taken by the entry, in bytes
+    fn size(&self) -> usize {
+        (self.engine.weight() + self.data.len()) / 8 + self.ends.len() * size_of::<u32>()
+    }
+
+    fn binary_data(&self) -> impl Iterator<Item = Bits> + '_ {
+        let ends = self.ends.iter().map(|end| *end as usize);
+        std::iter::once(0)
+            .chain(ends.clone())
+            .zip(ends)
+            .map(|(start, end)| self.data.slice(start..end))
+    }
+}
+
+#[derive(Default)]
+struct Memo {
+    size: usize,
+    multiple: HashMap<MemoKey, MemoEntry>,
+    one: HashMap<MemoKey, MemoEntry>,
+}
+
+impl Memo {
+    // Keys hold options, which may hold a context with a mutex, but the context of keys is always
+    // None
+    #[allow(clippy::mutable_key_type)]
+    fn insert(&mut self, one: bool, key: MemoKey, entry: Option<MemoEntry>) {
+        let Some(entry) = entry else {
+            return;
+        };
+        let size = key.size() + entry.size();
+        if self.size + size <= MEMO_BUDGET {
+            self.size += size;
+            let map = if one {
+                &mut self.one
+            } else {
+                &mut self.multiple
+            };
+            map.insert(key, entry);
+        }
+    }
+}
+
+/// State shared by the calls of one compression, i.e. results of subproblems that were already
+/// solved and, in explain mode, the alternatives rejected on the way.
+///
+/// [`autocompress`] creates a context for each call that doesn't get one in its options, so
+/// separate calls don't share anything unless they are given the same context. Contexts don't
+/// affect the results, so all of them compare equal.
+#[derive(Default)]
+pub struct SearchContext {
+    memo: Mutex<Memo>,
+    choices: Option<Mutex<Choices>>,
+}
+
+impl SearchContext {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub(crate) fn recording_choices() -> Self {
+        Self {
+            choices: Some(Mutex::default()),
+            ..Self::default()
+        }
+    }
+
+    pub(crate) fn into_choices(self) -> Choices {
+        self.choices
+            .map(|choices| choices.into_inner().unwrap_or_else(|err| err.into_inner()))
+            .unwrap_or_default()
+    }
+
+    fn memo(&self) -> MutexGuard<'_, Memo> {
+        self.memo.lock().unwrap_or_else(|err| err.into_inner())
+    }
+
+    fn choices(&self) -> Option<MutexGuard<'_, Choices>> {
+        let choices = self.choices.as_ref()?;
+        Some(choices.lock().unwrap_or_else(|err| err.into_inner()))
+    }
+}
+
+impl PartialEq for SearchContext {
+    fn eq(&self, _other: &Self) -> bool {
+        true
+    }
+}
+
+impl Eq for SearchContext {}
+
+impl Hash for SearchContext {
+    fn hash<H: Hasher>(&self, _state: &mut H) {}
+}
+
+impl Debug for SearchContext {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("SearchContext").finish_non_exhaustive()
+    }
+}
 
+/// Compresses a group of objects, picking the most efficient of the available strategies.
+///
+/// Independent strategies are tried in parallel, but the result doesn't depend on the number of
+/// threads.
 pub fn autocompress<T: Compress>(objs: &[&T], opts: AutoCompressOpts) -> MultiCompressedData {
     if objs.is_empty() {
         return MultiCompressedData {
@@ -140,66 +556,89 @@ pub fn autocompress<T: Compress>(objs: &[&T], opts: AutoCompressOpts) -> MultiCo
         };
     }
 
-    // println!(
-    //     "{}autocompress {objs:?} {opts:?}",
-    //     " ".repeat(unsafe { cc })
-    // );
-    unsafe {
-        cc += 1;
+    let Some(context) = opts.context else {
+        let context = SearchContext::new();
+        return autocompress(
+            objs,
+            AutoCompressOpts {
+                context: Some(&context),
+                ..opts
+            },
+        );
+    };
+
+    let key = MemoKey::new(objs, opts);
+    if let Some(entry) = context.memo().multiple.get(&key) {
+        return MultiCompressedData {
+            engine: entry.engine.clone(),
+            binary_data: entry.binary_data().collect(),
+        };
     }
 
-    if opts.enable_dedup_and_categories {
-        if let Some(data) = try_autocompress_dedup(objs) {
-            unsafe {
-                cc -= 1;
-            }
+    let data = autocompress_uncached(objs, opts);
+    let entry = MemoEntry::new(&data.engine, &data.binary_data);
+    context.memo().insert(false, key, entry);
+    data
+}
+
+fn autocompress_uncached<T: Compress>(objs: &[&T], opts: AutoCompressOpts) -> MultiCompressedData {
+    // Out of time, so only the direct encoding is left
+    if opts.expired() {
+        return T::compress_multiple(objs, opts);
+    }
+
+    if opts.enable_dedup_and_categories && opts.dedup() {
+        if let Some(data) = try_autocompress_dedup(objs, opts) {
             return data;
         }
+    }
 
-        if let Some(data) = try_autocompress_categories(objs) {
-            unsafe {
-                cc -= 1;
-            }
+    if opts.enable_dedup_and_categories && opts.categories() {
+        if let Some(categories) = T::split_categories(objs, opts).filter(|c| c.len() >= 2) {
             // This may be less efficient than direct compression
-            let data_direct = T::compress_multiple(objs, opts);
-            if data_direct.weight() < data.weight() {
-                return data_direct;
-            }
-            return data;
+            let (data, data_direct) = join(
+                || autocompress_categories(objs, categories, opts),
+                || T::compress_multiple(objs, opts),
+            );
+            return choose(vec![("categories", data), ("direct", data_direct)], opts);
         }
     }
 
-    if opts.enable_stateful && objs.len() > 1 {
-        let data = autocompress_stateful(objs, opts);
-        unsafe {
-            cc -= 1;
-        }
+    if opts.enable_stateful && opts.stateful() && objs.len() > 1 {
         // This may be less efficient than direct compression
-        let data_direct = T::compress_multiple(objs, opts);
-        if data_direct.weight() < data.weight() {
-            return data_direct;
-        }
-        return data;
+        let (data, data_direct) = join(
+            || autocompress_stateful(objs, opts),
+            || T::compress_multiple(objs, opts),
+        );
+        return choose(vec![("stateful", data), ("direct", data_direct)], opts);
     }
 
-    let data = T::compress_multiple(objs, opts);
-    unsafe {
-        cc -= 1;
-    }
-    data
+    T::compress_multiple(objs, opts)
 }
 
+/// Compresses a single object.
 pub fn autocompress_one<T: Compress>(obj: &T, opts: AutoCompressOpts) -> CompressedData {
-    // println!(
-    //     "{}autocompress_one {obj:?} {opts:?}",
-    //     " ".repeat(unsafe { cc })
-    // );
-    unsafe {
-        cc += 1;
+    let Some(context) = opts.context else {
+        let context = SearchContext::new();
+        return autocompress_one(
+            obj,
+            AutoCompressOpts {
+                context: Some(&context),
+                ..opts
+            },
+        );
+    };
+
+    let key = MemoKey::new(&[obj], opts);
+    if let Some(entry) = context.memo().one.get(&key) {
+        return CompressedData {
+            engine: entry.engine.clone(),
+            binary_data: entry.binary_data().next().unwrap(),
+        };
     }
+
     let data = obj.compress(opts);
-    unsafe {
-        cc -= 1;
-    }
+    let entry = MemoEntry::new(&data.engine, [&data.binary_data]);
+    context.memo().insert(true, key, entry);
     data
 }
@@ -1,51 +1,207 @@
+use crate::decompress::DecodeError;
 use std::fmt::Debug;
+use std::ops::Range;
 
-#[derive(Clone)]
+/// A growable sequence of bits.
+// Bits are packed into words starting from the least significant bit. Bits past the end are
+// always zero.
+#[derive(Clone, Default)]
 pub struct Bits {
-    bits: Vec<bool>,
+    words: Vec<u64>,
+    len: usize,
 }
 
 impl Bits {
     pub fn new() -> Self {
-        Self { bits: Vec::new() }
+        Self {
+            words: Vec::new(),
+            len: 0,
+        }
     }
 
     pub fn push(&mut self, x: bool) {
-        self.bits.push(x);
+        self.push_word(x as u64, 1);
     }
 
     pub fn pop(&mut self) -> Option<bool> {
-        self.bits.pop()
+        if self.len == 0 {
+            return None;
+        }
+        self.len -= 1;
+        let bit = self.get(self.len);
+        if self.len.is_multiple_of(64) {
+            self.words.pop();
+        } else {
+            self.words[self.len / 64] &= !(1 << (self.len % 64));
+        }
+        Some(bit)
+    }
+
+    pub fn get(&self, i: usize) -> bool {
+        ((self.words[i / 64] >> (i % 64)) & 1) != 0
+    }
+
+    // Appends the low bit_length bits of word, least significant first
+    fn push_word(&mut self, word: u64, bit_length: usize) {
+        if bit_length == 0 {
+            return;
+        }
+        let word = if bit_length < 64 {
+            word & ((1 << bit_length) - 1)
+        } else {
+            word
+        };
+        let offset = self.len % 64;
+        if offset == 0 {
+            self.words.push(word);
+        } else {
+            *self.words.last_mut().unwrap() |= word << offset;
+            if offset + bit_length > 64 {
+                self.words.push(word >> (64 - offset));
+            }
+        }
+        self.len += bit_length;
+    }
+
+    // Reads bit_length <= 64 bits starting at position, least significant first
+    fn read_word(&self, position: usize, bit_length: usize) -> u64 {
+        if bit_length == 0 {
+            return 0;
+        }
+        let offset = position % 64;
+        let mut word = self.words[position / 64] >> offset;
+        if offset + bit_length > 64 {
+            word |= self.words[position / 64 + 1] << (64 - offset);
+        }
+        if bit_length < 64 {
+            word &= (1 << bit_length) - 1;
+        }
+        word
+    }
+
+    // Appends the low bit_length bits of num, least significant first
+    pub fn push_int(&mut self, num: u128, bit_length: usize) {
+        assert!(bit_length <= 128);
+        self.push_word(num as u64, bit_length.min(64));
+        self.push_word((num >> 64) as u64, bit_length.saturating_sub(64));
     }
 
     pub fn extend(&mut self, rhs: &Bits) {
-        for x in &rhs.bits {
-            self.push(*x);
+        for (i, word) in rhs.words.iter().enumerate() {
+            self.push_word(*word, (rhs.len - i * 64).min(64));
         }
     }
 
     pub fn to_bytes(&self) -> Vec<u8> {
-        let mut res = Vec::new();
-        for chunk in self.bits.chunks(8) {
-            let mut byte = 0u8;
-            for b in chunk {
-                byte <<= 1;
-                byte |= *b as u8;
-            }
-            res.push(byte);
+        // Bytes are filled starting from the most significant bit. The last byte is padded with
+        // zeroes so that from_bytes reads bits in the same order
+        (0..self.len)
+            .step_by(8)
+            .map(|i| (self.read_word(i, (self.len - i).min(8)) as u8).reverse_bits())
+            .collect()
+    }
+
+    pub fn from_bytes(bytes: &[u8]) -> Self {
+        let mut bits = Self::new();
+        for byte in bytes {
+            bits.push_word(byte.reverse_bits() as u64, 8);
+        }
+        bits
+    }
+
+    pub fn slice(&self, range: Range<usize>) -> Self {
+        assert!(range.start <= range.end && range.end <= self.len);
+        let mut bits = Self::new();
+        for i in range.clone().step_by(64) {
+            let bit_length = (range.end - i).min(64);
+            bits.push_word(self.read_word(i, bit_length), bit_length);
         }
-        res
+        bits
     }
 
     pub fn len(&self) -> usize {
-        self.bits.len()
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
     }
 }
 
 impl Debug for Bits {
     fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
-        for bit in &self.bits {
-            write!(f, "{}", if *bit { 1 } else { 0 })?;
+        for i in 0..self.len {
+            write!(f, "{}", if self.get(i) { 1 } else { 0 })?;
+        }
+        Ok(())
+    }
+}
+
+/// Sequential reader over [`Bits`].
+// Lists may hold more objects than there are bits, e.g. repeated constants that take no bits, but
+// only this many more in total, so that a few bits of corrupted data can't make the decoder loop
+// and allocate without bound
+const MAX_FREE_ITEMS: usize = 1 << 24;
+
+pub struct BitReader<'a> {
+    bits: &'a Bits,
+    position: usize,
+    // Objects of lists read so far, see reserve_items
+    items: usize,
+}
+
+impl<'a> BitReader<'a> {
+    pub fn new(bits: &'a Bits) -> Self {
+        Self {
+            bits,
+            position: 0,
+            items: 0,
+        }
+    }
+
+    pub fn read(&mut self) -> Result<bool, DecodeError> {
+        if self.position >= self.bits.len {
+            return Err(DecodeError::UnexpectedEnd);
+        }
+        let bit = self.bits.get(self.position);
+        self.position += 1;
+        Ok(bit)
+    }
+
+    // Reads an integer written with Bits::push_int
+    pub fn read_int(&mut self, bit_length: usize) -> Result<u128, DecodeError> {
+        if bit_length > 128 {
+            return Err(DecodeError::InvalidData("integer is too long"));
+        }
+        if self.bits.len - self.position < bit_length {
+            return Err(DecodeError::UnexpectedEnd);
+        }
+        let low = self.bits.read_word(self.position, bit_length.min(64)) as u128;
+        let high =
+            self.bits
+                .read_word(self.position + 64, bit_length.saturating_sub(64)) as u128;
+        self.position += bit_length;
+        Ok(low | (high << 64))
+    }
+
+    pub fn position(&self) -> usize {
+        self.position
+    }
+
+    pub fn remaining(&self) -> usize {
+        self.bits.len - self.position
+    }
+
+    pub fn consumed_since(&self, start: usize) -> Bits {
+        self.bits.slice(start..self.position)
+    }
+
+    // Accounts for `n` more objects of a list before they are read. The length of a list comes
+    // from the data, so it's only trusted as long as the objects fit the limit
+    pub(crate) fn reserve_items(&mut self, n: usize) -> Result<(), DecodeError> {
+        self.items = self.items.saturating_add(n);
+        if self.items > self.bits.len.saturating_add(MAX_FREE_ITEMS) {
+            return Err(DecodeError::InvalidData("too many objects for the data"));
         }
         Ok(())
     }
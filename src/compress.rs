@@ -1,10 +1,14 @@
 use crate::autocompress::AutoCompressOpts;
 use crate::bits::Bits;
-use crate::varint::compress_varint;
+use crate::decompress::Value;
+use crate::schema::Schema;
+use crate::varint::{compress_varint, compress_varuint};
 
+use std::cmp::Ordering;
 use std::hash::Hash;
 
-#[derive(Debug)]
+/// A node of the tree that describes how a group of objects is compressed.
+#[derive(Clone, Debug)]
 pub enum Engine {
     VarInt,
     FixedInt {
@@ -30,6 +34,7 @@ pub enum Engine {
         separator: char,
     },
     IntSet {
+        length: Box<Engine>,
         min: Box<Engine>,
         max: Box<Engine>,
         unique: bool,
@@ -66,6 +71,45 @@ pub enum Engine {
         inner: Box<Engine>,
         precision: Box<Engine>,
     },
+    Bytes {
+        inner: Box<Engine>,
+    },
+    RangeCoded {
+        length: usize,
+        alphabet_engine: Box<Engine>,
+        alphabet_data: Bits,
+        freqs_engine: Box<Engine>,
+        freqs_data: Bits,
+        data: Bits,
+    },
+    RangeCodedInt {
+        bias: i128,
+        size: u128,
+        length: usize,
+        data: Bits,
+    },
+    ContextString {
+        order: usize,
+        length: usize,
+        alphabet_engine: Box<Engine>,
+        alphabet_data: Bits,
+        data: Bits,
+    },
+    Schema {
+        schema: Schema,
+        newline: Box<Engine>,
+        fields: Vec<Engine>,
+    },
+    // The number of lines of an answer is a token of its input file plus an offset
+    AnswerLines {
+        token: usize,
+        offset: i128,
+        newline: Box<Engine>,
+        line: Box<Engine>,
+    },
+    // Raw bytes, used when nothing else is smaller. Each object is stored as whether it is text,
+    // its length in bytes and the bytes
+    Stored,
 }
 
 impl Engine {
@@ -105,8 +149,8 @@ impl Engine {
                 bits.push(true);
                 bits.push(false);
                 alphabet_engine.push_to_bits(bits);
-                bits.extend(&alphabet_data);
-                bits.extend(&tree);
+                bits.extend(alphabet_data);
+                bits.extend(tree);
             }
             Self::CanonicalHuffman {
                 alphabet_engine,
@@ -119,9 +163,9 @@ impl Engine {
                 bits.push(true);
                 bits.push(true);
                 alphabet_engine.push_to_bits(bits);
-                bits.extend(&alphabet_data);
+                bits.extend(alphabet_data);
                 lengths_engine.push_to_bits(bits);
-                bits.extend(&lengths_data);
+                bits.extend(lengths_data);
             }
             Self::String { chars } => {
                 bits.push(false);
@@ -138,12 +182,18 @@ impl Engine {
                 bits.extend(&compress_varint(*separator as i128));
                 words.push_to_bits(bits);
             }
-            Self::IntSet { min, max, unique } => {
+            Self::IntSet {
+                length,
+                min,
+                max,
+                unique,
+            } => {
                 bits.push(false);
                 bits.push(true);
                 bits.push(true);
                 bits.push(false);
                 bits.push(*unique);
+                length.push_to_bits(bits);
                 min.push_to_bits(bits);
                 max.push_to_bits(bits);
             }
@@ -153,7 +203,7 @@ impl Engine {
                 bits.push(true);
                 bits.push(true);
                 inner.push_to_bits(bits);
-                bits.extend(&data);
+                bits.extend(data);
             }
             Self::Vec { length, item } => {
                 bits.push(true);
@@ -178,6 +228,7 @@ impl Engine {
                 bits.push(true);
                 bits.push(false);
                 bits.push(true);
+                bits.push(false);
                 bits.extend(&compress_varint(categories.len() as i128));
                 for cat in categories {
                     cat.push_to_bits(bits);
@@ -220,15 +271,122 @@ impl Engine {
                 inner.push_to_bits(bits);
                 precision.push_to_bits(bits);
             }
+            // The 1011 prefix is followed by an index of a less common engine
+            Self::Bytes { inner } => {
+                bits.push(true);
+                bits.push(false);
+                bits.push(true);
+                bits.push(true);
+                bits.extend(&compress_varuint(0));
+                inner.push_to_bits(bits);
+            }
+            Self::RangeCoded {
+                length,
+                alphabet_engine,
+                alphabet_data,
+                freqs_engine,
+                freqs_data,
+                data,
+            } => {
+                bits.push(true);
+                bits.push(false);
+                bits.push(true);
+                bits.push(true);
+                bits.extend(&compress_varuint(1));
+                bits.extend(&compress_varuint(*length as u128));
+                alphabet_engine.push_to_bits(bits);
+                bits.extend(alphabet_data);
+                freqs_engine.push_to_bits(bits);
+                bits.extend(freqs_data);
+                bits.extend(data);
+            }
+            Self::RangeCodedInt {
+                bias,
+                size,
+                length,
+                data,
+            } => {
+                bits.push(true);
+                bits.push(false);
+                bits.push(true);
+                bits.push(true);
+                bits.extend(&compress_varuint(2));
+                bits.extend(&compress_varint(*bias));
+                bits.extend(&compress_varuint(*size));
+                bits.extend(&compress_varuint(*length as u128));
+                bits.extend(data);
+            }
+            Self::ContextString {
+                order,
+                length,
+                alphabet_engine,
+                alphabet_data,
+                data,
+            } => {
+                bits.push(true);
+                bits.push(false);
+                bits.push(true);
+                bits.push(true);
+                bits.extend(&compress_varuint(3));
+                bits.extend(&compress_varuint(*order as u128));
+                bits.extend(&compress_varuint(*length as u128));
+                alphabet_engine.push_to_bits(bits);
+                bits.extend(alphabet_data);
+                bits.extend(data);
+            }
+            // The number of fields is known from the schema
+            Self::Schema {
+                schema,
+                newline,
+                fields,
+            } => {
+                bits.push(true);
+                bits.push(false);
+                bits.push(true);
+                bits.push(true);
+                bits.extend(&compress_varuint(4));
+                schema.push_to_bits(bits);
+                newline.push_to_bits(bits);
+                for field in fields {
+                    field.push_to_bits(bits);
+                }
+            }
+            Self::AnswerLines {
+                token,
+                offset,
+                newline,
+                line,
+            } => {
+                bits.push(true);
+                bits.push(false);
+                bits.push(true);
+                bits.push(true);
+                bits.extend(&compress_varuint(5));
+                bits.extend(&compress_varuint(*token as u128));
+                bits.extend(&compress_varint(*offset));
+                newline.push_to_bits(bits);
+                line.push_to_bits(bits);
+            }
+            Self::Stored => {
+                bits.push(true);
+                bits.push(false);
+                bits.push(true);
+                bits.push(true);
+                bits.extend(&compress_varuint(6));
+            }
         }
     }
 }
 
+/// A single object compressed on its own.
+#[derive(Clone)]
 pub struct CompressedData {
     pub engine: Engine,
     pub binary_data: Bits,
 }
 
+/// A group of objects compressed with a common engine, with binary data for each object.
+#[derive(Clone)]
 pub struct MultiCompressedData {
     pub engine: Engine,
     pub binary_data: Vec<Bits>,
@@ -248,12 +406,39 @@ impl MultiCompressedData {
             .sum::<usize>()
             + self.engine.weight()
     }
+
+    // The engine followed by binary data of each object, as expected by decode_group
+    pub fn to_bits(&self) -> Bits {
+        let mut bits = self.engine.to_bits();
+        for chunk in &self.binary_data {
+            bits.extend(chunk);
+        }
+        bits
+    }
 }
 
-pub trait Compress: Eq + Hash + std::fmt::Debug {
+/// Types that can be compressed by [`autocompress`](crate::autocompress).
+pub trait Compress: Eq + Hash + std::fmt::Debug + Sync {
+    /// Compresses a single object.
     fn compress(&self, opts: AutoCompressOpts) -> CompressedData;
+    /// Compresses a group of objects directly, without deduplication or category splitting.
     fn compress_multiple(objs: &[&Self], opts: AutoCompressOpts) -> MultiCompressedData;
-    fn split_categories(objs: &[&Self]) -> Option<Vec<Vec<usize>>>;
+    /// Splits objects into groups of indices that are worth compressing separately.
+    fn split_categories(objs: &[&Self], opts: AutoCompressOpts) -> Option<Vec<Vec<usize>>>;
+    /// Converts the object to the value it is decoded as.
+    fn to_value(&self) -> Value;
+
+    /// Compares two objects of a type with a total order, which enables ordered Huffman codes.
+    /// `None` if objects are not ordered, which is the default.
+    fn compare(&self, _other: &Self) -> Option<Ordering> {
+        None
+    }
+
+    /// Converts the object to an integer if sorted vectors of such objects are worth compressing
+    /// as sets. `None` by default.
+    fn to_int(&self) -> Option<i128> {
+        None
+    }
 }
 
 impl<T: Compress> Compress for &T {
@@ -264,8 +449,17 @@ impl<T: Compress> Compress for &T {
         let refs: Vec<&T> = objs.iter().map(|obj| **obj).collect();
         T::compress_multiple(&refs, opts)
     }
-    fn split_categories(objs: &[&Self]) -> Option<Vec<Vec<usize>>> {
+    fn split_categories(objs: &[&Self], opts: AutoCompressOpts) -> Option<Vec<Vec<usize>>> {
         let refs: Vec<&T> = objs.iter().map(|obj| **obj).collect();
-        T::split_categories(&refs)
+        T::split_categories(&refs, opts)
+    }
+    fn to_value(&self) -> Value {
+        (*self).to_value()
+    }
+    fn compare(&self, other: &Self) -> Option<Ordering> {
+        (*self).compare(other)
+    }
+    fn to_int(&self) -> Option<i128> {
+        (*self).to_int()
     }
 }
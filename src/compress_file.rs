@@ -0,0 +1,109 @@
+use crate::autocompress::{join_categories, AutoCompressOpts};
+use crate::compress::{Compress, CompressedData, Engine, MultiCompressedData};
+use crate::decompress::Value;
+
+/// Contents of a file, which is compressed as text if it's valid UTF-8.
+#[derive(Clone, PartialEq, Eq, Hash, Debug)]
+pub enum FileContents {
+    Text(String),
+    Binary(Vec<u8>),
+}
+
+impl FileContents {
+    pub fn from_bytes(bytes: Vec<u8>) -> Self {
+        match String::from_utf8(bytes) {
+            Ok(s) => Self::Text(s),
+            Err(err) => Self::Binary(err.into_bytes()),
+        }
+    }
+
+    pub fn as_bytes(&self) -> &[u8] {
+        match self {
+            Self::Text(s) => s.as_bytes(),
+            Self::Binary(bytes) => bytes,
+        }
+    }
+}
+
+impl Compress for FileContents {
+    fn compress(&self, opts: AutoCompressOpts) -> CompressedData {
+        let mut data = Self::compress_multiple(&[self], opts);
+        CompressedData {
+            engine: data.engine,
+            binary_data: data.binary_data.pop().unwrap(),
+        }
+    }
+
+    fn compress_multiple(objs: &[&Self], opts: AutoCompressOpts) -> MultiCompressedData {
+        // Text and binary files are kept in separate categories, as a single engine would decode
+        // all of them as the same kind
+        let Some(categories) = Self::split_categories(objs, opts) else {
+            return compress_kind(objs, opts);
+        };
+        let mut category_by_obj = vec![0; objs.len()];
+        for (i, category) in categories.iter().enumerate() {
+            for j in category {
+                category_by_obj[*j] = i;
+            }
+        }
+        let category_by_obj_refs: Vec<&usize> = category_by_obj.iter().collect();
+        let categories_compressed = categories
+            .iter()
+            .map(|category| {
+                let category_objs: Vec<&Self> = category.iter().map(|j| objs[*j]).collect();
+                compress_kind(&category_objs, opts)
+            })
+            .collect();
+        join_categories(
+            &categories,
+            usize::compress_multiple(&category_by_obj_refs, opts),
+            categories_compressed,
+        )
+    }
+
+    fn split_categories(objs: &[&Self], _opts: AutoCompressOpts) -> Option<Vec<Vec<usize>>> {
+        // By kind. This is not subject to the usual limit on the number of categories, as text
+        // compresses much better separately even if there are few files
+        let (texts, binaries): (Vec<usize>, Vec<usize>) =
+            (0..objs.len()).partition(|i| matches!(objs[*i], Self::Text(_)));
+        if texts.is_empty() || binaries.is_empty() {
+            None
+        } else {
+            Some(vec![texts, binaries])
+        }
+    }
+
+    fn to_value(&self) -> Value {
+        match self {
+            Self::Text(s) => Value::Str(s.clone()),
+            Self::Binary(bytes) => Value::Bytes(bytes.clone()),
+        }
+    }
+}
+
+// Compresses files of the same kind
+fn compress_kind(objs: &[&FileContents], opts: AutoCompressOpts) -> MultiCompressedData {
+    let texts: Option<Vec<&String>> = objs
+        .iter()
+        .map(|obj| match obj {
+            FileContents::Text(s) => Some(s),
+            FileContents::Binary(_) => None,
+        })
+        .collect();
+    if let Some(texts) = texts {
+        return String::compress_multiple(&texts, opts);
+    }
+
+    let bytes: Vec<Vec<&u8>> = objs
+        .iter()
+        .map(|obj| obj.as_bytes().iter().collect())
+        .collect();
+    let bytes_refs: Vec<&Vec<&u8>> = bytes.iter().collect();
+    let data = Vec::<&u8>::compress_multiple(&bytes_refs, opts);
+    MultiCompressedData {
+        engine: Engine::Bytes {
+            inner: Box::new(data.engine),
+        },
+        binary_data: data.binary_data,
+    }
+}
@@ -1,6 +1,10 @@
-use crate::autocompress::AutoCompressOpts;
+use crate::autocompress::{choose, AutoCompressOpts};
+use crate::bits::Bits;
 use crate::compress::{Compress, CompressedData, Engine, MultiCompressedData};
+use crate::decompress::Value;
+use crate::range_coding::encode_range_coded_ints;
 use crate::varint::{compress_fixint, compress_varint, get_bit_length};
+use std::cmp::Ordering;
 
 impl Compress for i128 {
     fn compress(&self, _opts: AutoCompressOpts) -> CompressedData {
@@ -10,7 +14,7 @@ impl Compress for i128 {
         }
     }
 
-    fn compress_multiple(objs: &[&Self], _opts: AutoCompressOpts) -> MultiCompressedData {
+    fn compress_multiple(objs: &[&Self], opts: AutoCompressOpts) -> MultiCompressedData {
         // Constant
         if objs.len() <= 1 {
             return MultiCompressedData {
@@ -25,7 +29,7 @@ impl Compress for i128 {
 
         let bit_length = get_bit_length((max - min) as u128);
 
-        MultiCompressedData {
+        let fixed = MultiCompressedData {
             engine: Engine::FixedInt {
                 bias: min,
                 length: bit_length,
@@ -34,16 +38,47 @@ impl Compress for i128 {
                 .iter()
                 .map(|num| compress_fixint((**num - min) as u128, bit_length))
                 .collect(),
+        };
+
+        // If the range is not a power of two, fixed-length encoding wastes a fraction of a bit per
+        // number, which range coding avoids
+        if let Some(size) = ((max - min) as u128).checked_add(1) {
+            if !size.is_power_of_two() {
+                let nums: Vec<u128> = objs.iter().map(|num| (**num - min) as u128).collect();
+                let range_coded = MultiCompressedData {
+                    engine: Engine::RangeCodedInt {
+                        bias: min,
+                        size,
+                        length: objs.len(),
+                        data: encode_range_coded_ints(&nums, size),
+                    },
+                    binary_data: vec![Bits::new(); objs.len()],
+                };
+                return choose(
+                    vec![("fixed width", fixed), ("range coding", range_coded)],
+                    opts,
+                );
+            }
         }
+
+        fixed
     }
 
-    fn split_categories(_objs: &[&Self]) -> Option<Vec<Vec<usize>>> {
+    fn split_categories(_objs: &[&Self], _opts: AutoCompressOpts) -> Option<Vec<Vec<usize>>> {
         None
     }
+
+    fn to_value(&self) -> Value {
+        Value::Int(*self)
+    }
+
+    fn compare(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
 }
 
 macro_rules! impl_int {
-    ($($t:ty),*) => {
+    ($int_set:literal: $($t:ty),*) => {
         $(impl Compress for $t {
             fn compress(&self, opts: AutoCompressOpts) -> CompressedData {
                 (*self as i128).compress(opts)
@@ -55,11 +90,25 @@ macro_rules! impl_int {
                 i128::compress_multiple(&objs, opts)
             }
 
-            fn split_categories(_objs: &[&Self]) -> Option<Vec<Vec<usize>>> {
+            fn split_categories(_objs: &[&Self], _opts: AutoCompressOpts) -> Option<Vec<Vec<usize>>> {
                 None
             }
+
+            fn to_value(&self) -> Value {
+                Value::Int(*self as i128)
+            }
+
+            fn compare(&self, other: &Self) -> Option<Ordering> {
+                Some(self.cmp(other))
+            }
+
+            fn to_int(&self) -> Option<i128> {
+                $int_set.then_some(*self as i128)
+            }
         })*
     }
 }
 
-impl_int!(u8, u16, u32, u64, usize, i8, i16, i32, i64, isize, char);
+// Sorted sets are only worth trying for bytes
+impl_int!(true: u8);
+impl_int!(false: u16, u32, u64, usize, i8, i16, i32, i64, isize, char);
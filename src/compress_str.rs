@@ -1,10 +1,13 @@
-use crate::autocompress::{autocompress, AutoCompressOpts};
+use crate::autocompress::{autocompress, choose, AutoCompressOpts};
 use crate::compress::{Compress, CompressedData, Engine, MultiCompressedData};
+use crate::context_model::context_coded;
+use crate::decompress::Value;
 use crate::split::try_split_by;
+use std::cmp::Ordering;
 
 impl Compress for String {
     fn compress(&self, opts: AutoCompressOpts) -> CompressedData {
-        let mut data = Self::compress_multiple(&[&self], opts);
+        let mut data = Self::compress_multiple(&[self], opts);
         CompressedData {
             engine: data.engine,
             binary_data: data.binary_data.pop().unwrap(),
@@ -14,11 +17,11 @@ impl Compress for String {
     fn compress_multiple(objs: &[&Self], opts: AutoCompressOpts) -> MultiCompressedData {
         // Text separation
         for separator in ['\n', ' '] {
-            if objs
-                .iter()
-                .map(|s| s.matches(separator).count())
-                .sum::<usize>()
-                >= objs.len()
+            if opts.separators()
+                && opts.separator_pays_off(
+                    objs.iter().map(|s| s.matches(separator).count()).sum(),
+                    objs.len(),
+                )
             {
                 let words: Vec<Vec<String>> = objs
                     .iter()
@@ -30,7 +33,9 @@ impl Compress for String {
                         }
                     })
                     .collect();
-                let words_refs: Vec<&Vec<String>> = words.iter().collect();
+                let words: Vec<Vec<&String>> =
+                    words.iter().map(|words| words.iter().collect()).collect();
+                let words_refs: Vec<&Vec<&String>> = words.iter().collect();
                 let words_compressed = autocompress(&words_refs, opts);
                 return MultiCompressedData {
                     engine: Engine::StringConcat {
@@ -100,28 +105,50 @@ impl Compress for String {
         }
 
         let chars: Vec<Vec<char>> = objs.iter().map(|s| s.chars().collect()).collect();
-        let chars_refs: Vec<&Vec<char>> = chars.iter().collect();
+        let chars: Vec<Vec<&char>> = chars.iter().map(|chars| chars.iter().collect()).collect();
+        let chars_refs: Vec<&Vec<&char>> = chars.iter().collect();
         let compressed = autocompress(&chars_refs, opts);
 
-        MultiCompressedData {
+        let plain = MultiCompressedData {
             engine: Engine::String {
                 chars: Box::new(compressed.engine),
             },
             binary_data: compressed.binary_data,
+        };
+
+        // Natural text is predicted much better by the preceding characters than by their overall
+        // frequencies
+        if opts.context_modeling() {
+            if let Some(context_coded) = context_coded(objs, opts) {
+                return choose(
+                    vec![("characters", plain), ("context model", context_coded)],
+                    opts,
+                );
+            }
         }
+
+        plain
     }
 
-    fn split_categories(objs: &[&Self]) -> Option<Vec<Vec<usize>>> {
+    fn split_categories(objs: &[&Self], opts: AutoCompressOpts) -> Option<Vec<Vec<usize>>> {
         // By kind
-        if let Some(categories) = try_split_by(objs, |s| get_string_kind(s)) {
+        if let Some(categories) = try_split_by(objs, opts, |s| get_string_kind(s)) {
             return Some(categories);
         }
         None
     }
+
+    fn to_value(&self) -> Value {
+        Value::Str(self.clone())
+    }
+
+    fn compare(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
 }
 
-#[derive(PartialEq, Eq, Hash)]
-enum StringKind {
+#[derive(Clone, Copy, PartialEq, Eq, Hash)]
+pub(crate) enum StringKind {
     Empty,
     DecimalNumber,
     ExtendedDecimalNumber,
@@ -131,29 +158,29 @@ enum StringKind {
     Generic,
 }
 
-fn get_string_kind(s: &str) -> StringKind {
+pub(crate) fn get_string_kind(s: &str) -> StringKind {
     if s.is_empty() {
-        return StringKind::Empty;
-    } else if s.chars().all(|c| c.is_digit(10)) {
-        return StringKind::DecimalNumber;
+        StringKind::Empty
+    } else if s.chars().all(|c| c.is_ascii_digit()) {
+        StringKind::DecimalNumber
     } else if s
         .chars()
-        .all(|c| c == '+' || c == '-' || c == '.' || c == 'e' || c == 'E' || c.is_digit(10))
+        .all(|c| c == '+' || c == '-' || c == '.' || c == 'e' || c == 'E' || c.is_ascii_digit())
     {
-        return StringKind::ExtendedDecimalNumber;
+        StringKind::ExtendedDecimalNumber
     } else if s
         .chars()
-        .all(|c| ('a' <= c && c <= 'z') || ('A' <= c && c <= 'Z'))
+        .all(|c: char| c.is_ascii_lowercase() || c.is_ascii_uppercase())
     {
-        return StringKind::Latin;
+        StringKind::Latin
     } else if s
         .chars()
-        .all(|c| ('a' <= c && c <= 'z') || ('A' <= c && c <= 'Z') || c.is_digit(10))
+        .all(|c: char| c.is_ascii_lowercase() || c.is_ascii_uppercase() || c.is_ascii_digit())
     {
-        return StringKind::LatinNumeric;
+        StringKind::LatinNumeric
     } else if s.chars().all(|c| (c as u32) < 128) {
-        return StringKind::Text;
+        StringKind::Text
     } else {
-        return StringKind::Generic;
+        StringKind::Generic
     }
 }
@@ -1,8 +1,10 @@
 use crate::autocompress::{autocompress, autocompress_one, AutoCompressOpts};
 use crate::bits::Bits;
 use crate::compress::{Compress, CompressedData, Engine, MultiCompressedData};
+use crate::decompress::Value;
 use crate::split::try_split_by;
-use crate::varint::{compress_fixint, get_bit_length};
+use crate::varint::get_bit_length;
+use std::cmp::Ordering;
 
 impl<T: Compress> Compress for Vec<&T> {
     fn compress(&self, opts: AutoCompressOpts) -> CompressedData {
@@ -20,10 +22,10 @@ impl<T: Compress> Compress for Vec<&T> {
             l = r;
         }
 
-        if run_lengths.len() < self.len() / 2 {
-            let run_lengths_compressed =
-                autocompress_one(&run_lengths, AutoCompressOpts::default());
-            let run_values_compressed = autocompress_one(&run_values, AutoCompressOpts::default());
+        if opts.rle() && opts.rle_pays_off(run_lengths.len(), self.len()) {
+            let run_lengths: Vec<&usize> = run_lengths.iter().collect();
+            let run_lengths_compressed = autocompress_one(&run_lengths, opts.subproblem());
+            let run_values_compressed = autocompress_one(&run_values, opts.subproblem());
 
             let mut binary_data = run_lengths_compressed.binary_data;
             binary_data.extend(&run_values_compressed.binary_data);
@@ -37,7 +39,7 @@ impl<T: Compress> Compress for Vec<&T> {
             };
         }
 
-        if let Some(mut data) = <T as EncodeVecSorted>::encode_vec_sorted(&[&self], opts) {
+        if let Some(mut data) = encode_vec_sorted(&[self], opts) {
             return CompressedData {
                 engine: data.engine,
                 binary_data: data.binary_data.pop().unwrap(),
@@ -45,7 +47,7 @@ impl<T: Compress> Compress for Vec<&T> {
         }
 
         // No compression
-        let mut data = encode_vec_raw(&[&self], opts);
+        let mut data = encode_vec_raw(&[self], opts);
         CompressedData {
             engine: data.engine,
             binary_data: data.binary_data.pop().unwrap(),
@@ -72,21 +74,22 @@ impl<T: Compress> Compress for Vec<&T> {
                 (run_lengths, run_values)
             })
             .collect();
-        if objs_rle
+        let n_runs = objs_rle
             .iter()
             .map(|(run_lengths, _)| run_lengths.len())
-            .sum::<usize>()
-            < objs.iter().map(|vec| vec.len()).sum::<usize>() / 2
-        {
-            let run_lengths: Vec<&Vec<usize>> = objs_rle
+            .sum();
+        let total_length = objs.iter().map(|vec| vec.len()).sum();
+        if opts.rle() && opts.rle_pays_off(n_runs, total_length) {
+            let run_lengths: Vec<Vec<&usize>> = objs_rle
                 .iter()
-                .map(|(run_lengths, _)| run_lengths)
+                .map(|(run_lengths, _)| run_lengths.iter().collect())
                 .collect();
+            let run_lengths: Vec<&Vec<&usize>> = run_lengths.iter().collect();
             let run_values: Vec<&Vec<&T>> =
                 objs_rle.iter().map(|(_, run_values)| run_values).collect();
 
-            let run_lengths_compressed = autocompress(&run_lengths, AutoCompressOpts::default());
-            let run_values_compressed = autocompress(&run_values, AutoCompressOpts::default());
+            let run_lengths_compressed = autocompress(&run_lengths, opts.subproblem());
+            let run_values_compressed = autocompress(&run_values, opts.subproblem());
 
             let mut binary_data = run_lengths_compressed.binary_data;
             for (i, bits) in binary_data.iter_mut().enumerate() {
@@ -102,7 +105,7 @@ impl<T: Compress> Compress for Vec<&T> {
             };
         }
 
-        if let Some(data) = <T as EncodeVecSorted>::encode_vec_sorted(objs, opts) {
+        if let Some(data) = encode_vec_sorted(objs, opts) {
             return data;
         }
 
@@ -110,52 +113,49 @@ impl<T: Compress> Compress for Vec<&T> {
         encode_vec_raw(objs, opts)
     }
 
-    fn split_categories(objs: &[&Self]) -> Option<Vec<Vec<usize>>> {
+    fn split_categories(objs: &[&Self], opts: AutoCompressOpts) -> Option<Vec<Vec<usize>>> {
         // By length
-        if let Some(categories) = try_split_by(objs, |vec| vec.len()) {
+        if let Some(categories) = try_split_by(objs, opts, |vec| vec.len()) {
             return Some(categories);
         }
         // By item
         let min_length = objs.iter().map(|vec| vec.len()).min()?;
         for key in 0..min_length {
-            if let Some(categories) = try_split_by(objs, |vec| &vec[key]) {
+            if let Some(categories) = try_split_by(objs, opts, |vec| &vec[key]) {
                 return Some(categories);
             }
         }
         None
     }
-}
-
-impl<T: Compress> Compress for Vec<T> {
-    default fn compress(&self, opts: AutoCompressOpts) -> CompressedData {
-        let refs: Vec<&T> = self.iter().collect();
-        refs.compress(opts)
-    }
 
-    default fn compress_multiple(objs: &[&Self], opts: AutoCompressOpts) -> MultiCompressedData {
-        let vecs: Vec<Vec<&T>> = objs.iter().map(|vec| vec.iter().collect()).collect();
-        let refs: Vec<&Vec<&T>> = vecs.iter().collect();
-        Vec::<&T>::compress_multiple(&refs, opts)
+    fn to_value(&self) -> Value {
+        Value::Vec(self.iter().map(|item| item.to_value()).collect())
     }
 
-    default fn split_categories(objs: &[&Self]) -> Option<Vec<Vec<usize>>> {
-        let vecs: Vec<Vec<&T>> = objs.iter().map(|vec| vec.iter().collect()).collect();
-        let refs: Vec<&Vec<&T>> = vecs.iter().collect();
-        Vec::<&T>::split_categories(&refs)
+    fn compare(&self, other: &Self) -> Option<Ordering> {
+        // Lexicographic
+        for (a, b) in self.iter().zip(other.iter()) {
+            let ordering = a.compare(b)?;
+            if ordering != Ordering::Equal {
+                return Some(ordering);
+            }
+        }
+        Some(self.len().cmp(&other.len()))
     }
 }
 
 fn encode_vec_raw<T: Compress>(objs: &[&Vec<&T>], opts: AutoCompressOpts) -> MultiCompressedData {
     let lengths: Vec<usize> = objs.iter().map(|vec| vec.len()).collect();
     let lengths_refs: Vec<&usize> = lengths.iter().collect();
-    let lengths_compressed = autocompress(&lengths_refs, AutoCompressOpts::default());
+    let lengths_compressed = autocompress(&lengths_refs, opts.subproblem());
 
-    let items: Vec<&T> = objs.iter().map(|vec| *vec).flatten().map(|x| *x).collect();
+    let items: Vec<&T> = objs.iter().copied().flatten().copied().collect();
     let items_compressed = autocompress(
         &items,
         AutoCompressOpts {
             enable_dedup_and_categories: opts.enable_dedup_and_categories,
             enable_stateful: false,
+            ..opts
         },
     );
 
@@ -179,65 +179,77 @@ fn encode_vec_raw<T: Compress>(objs: &[&Vec<&T>], opts: AutoCompressOpts) -> Mul
     }
 }
 
-trait EncodeVecSorted {
-    fn encode_vec_sorted(
-        objs: &[&Vec<&Self>],
-        opts: AutoCompressOpts,
-    ) -> Option<MultiCompressedData>;
-}
-
-impl<T> EncodeVecSorted for T {
-    default fn encode_vec_sorted(
-        _objs: &[&Vec<&Self>],
-        _opts: AutoCompressOpts,
-    ) -> Option<MultiCompressedData> {
-        None
+fn encode_vec_sorted<T: Compress>(
+    objs: &[&Vec<&T>],
+    opts: AutoCompressOpts,
+) -> Option<MultiCompressedData> {
+    if !opts.int_set() || objs.is_empty() || objs.iter().any(|vec| vec.is_empty()) {
+        return None;
+    }
+    let sets: Vec<Vec<i128>> = objs
+        .iter()
+        .map(|vec| vec.iter().map(|x| x.to_int()).collect())
+        .collect::<Option<_>>()?;
+    if !sets
+        .iter()
+        .all(|nums| nums.windows(2).all(|window| window[0] <= window[1]))
+    {
+        return None;
     }
-}
 
-macro_rules! impl_int {
-    ($($t:ty),*) => {
-        $(impl EncodeVecSorted for $t {
-            fn encode_vec_sorted(objs: &[&Vec<&Self>], opts: AutoCompressOpts) -> Option<MultiCompressedData> {
-                if objs.is_empty() || objs.iter().all(|vec| vec.is_empty()) || !objs.iter().all(|vec| vec.windows(2).all(|window| window[0] <= window[1])) {
-                    return None;
-                }
+    // Unique encoding only works correctly if there are at least two items in each set
+    let unique = sets
+        .iter()
+        .all(|nums| nums.len() >= 2 && nums.windows(2).all(|window| window[0] != window[1]));
 
-                // Unique encoding only works correctly if there are at least two items in each set
-                let unique = objs.iter().all(|vec| vec.len() >= 2 && vec.windows(2).all(|window| window[0] != window[1]));
+    let min_elems: Vec<&T> = objs.iter().map(|vec| vec[0]).collect();
+    let max_elems: Vec<&T> = objs.iter().map(|vec| vec[vec.len() - 1]).collect();
 
-                let min_elems: Vec<&Self> = objs.iter().filter_map(|vec| vec.first().cloned()).collect();
-                let max_elems: Vec<&Self> = objs.iter().filter_map(|vec| vec.last().cloned()).collect();
+    let lengths: Vec<usize> = objs.iter().map(|vec| vec.len()).collect();
+    let lengths_refs: Vec<&usize> = lengths.iter().collect();
 
-                let min_elems_compressed = autocompress(&min_elems, opts);
-                let max_elems_compressed = autocompress(&max_elems, opts);
+    let lengths_compressed = autocompress(&lengths_refs, opts);
+    let min_elems_compressed = autocompress(&min_elems, opts);
+    let max_elems_compressed = autocompress(&max_elems, opts);
 
-                Some(MultiCompressedData {
-                    engine: Engine::IntSet {
-                        min: Box::new(min_elems_compressed.engine),
-                        max: Box::new(max_elems_compressed.engine),
-                        unique,
-                    },
-                    binary_data: objs.iter().enumerate().map(|(i, vec)| {
-                        let nums: Vec<i128> = vec.iter().map(|x| **x as i128).collect();
-                        let mut bits = min_elems_compressed.binary_data[i].clone();
-                        bits.extend(&max_elems_compressed.binary_data[i]);
-                        if unique {
-                            // As the min and max values are known, there's no need to list them
-                            encode_ordered_set_slice(&nums[1..nums.len() - 1], nums[0] + 1, *nums.last().unwrap() - 1, true, &mut bits);
-                        } else {
-                            encode_ordered_set_slice(&nums, nums[0], *nums.last().unwrap(), false, &mut bits);
-                        }
-                        bits
-                    }).collect(),
-                })
-            }
-        })*
-    }
+    Some(MultiCompressedData {
+        engine: Engine::IntSet {
+            length: Box::new(lengths_compressed.engine),
+            min: Box::new(min_elems_compressed.engine),
+            max: Box::new(max_elems_compressed.engine),
+            unique,
+        },
+        binary_data: sets
+            .iter()
+            .enumerate()
+            .map(|(i, nums)| {
+                let mut bits = lengths_compressed.binary_data[i].clone();
+                bits.extend(&min_elems_compressed.binary_data[i]);
+                bits.extend(&max_elems_compressed.binary_data[i]);
+                if unique {
+                    // As the min and max values are known, there's no need to list them
+                    encode_ordered_set_slice(
+                        &nums[1..nums.len() - 1],
+                        nums[0] + 1,
+                        *nums.last().unwrap() - 1,
+                        true,
+                        &mut bits,
+                    );
+                } else {
+                    encode_ordered_set_slice(
+                        nums,
+                        nums[0],
+                        *nums.last().unwrap(),
+                        false,
+                        &mut bits,
+                    );
+                }
+                bits
+            })
+            .collect(),
+    })
 }
 
-impl_int!(u8); //, u16, u32, u64, usize, i8, i16, i32, i64, isize, char, i128);
-
 fn encode_ordered_set_slice(slice: &[i128], min: i128, max: i128, unique: bool, bits: &mut Bits) {
     if slice.is_empty() {
         return;
@@ -249,12 +261,12 @@ fn encode_ordered_set_slice(slice: &[i128], min: i128, max: i128, unique: bool,
         let m_min = min + (m as i128);
         let m_max = max - (slice.len() - m - 1) as i128;
         let bit_length = get_bit_length((m_max - m_min) as u128);
-        bits.extend(&compress_fixint((slice[m] - m_min) as u128, bit_length));
+        bits.push_int((slice[m] - m_min) as u128, bit_length);
         encode_ordered_set_slice(&slice[..m], min, slice[m] - 1, true, bits);
         encode_ordered_set_slice(&slice[m + 1..], slice[m] + 1, max, true, bits);
     } else {
         let bit_length = get_bit_length((max - min) as u128);
-        bits.extend(&compress_fixint((slice[m] - min) as u128, bit_length));
+        bits.push_int((slice[m] - min) as u128, bit_length);
         encode_ordered_set_slice(&slice[..m], min, slice[m], false, bits);
         encode_ordered_set_slice(&slice[m + 1..], slice[m], max, false, bits);
     }
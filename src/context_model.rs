@@ -0,0 +1,164 @@
+use crate::autocompress::{autocompress_one, AutoCompressOpts};
+use crate::bits::{BitReader, Bits};
+use crate::compress::{Engine, MultiCompressedData};
+use crate::decompress::DecodeError;
+use crate::parallel::map;
+use crate::range_coding::{check_length, RangeDecoder, RangeEncoder, MAX_TOTAL};
+use std::collections::{BTreeSet, HashMap};
+
+// Adaptive order-k model: each symbol is range-coded with frequencies collected from the symbols
+// that previously followed the same k symbols. Every string is terminated with an extra end
+// symbol, so the model also learns where strings tend to end.
+const INCREMENT: u32 = 32;
+
+pub const MAX_ALPHABET: usize = 256;
+pub const MAX_ORDER: usize = 2;
+
+struct ContextModel {
+    order: usize,
+    n_symbols: usize,
+    contexts: HashMap<Vec<usize>, Vec<u32>>,
+}
+
+impl ContextModel {
+    fn new(alphabet_len: usize, order: usize) -> Self {
+        Self {
+            order,
+            // The last symbol is the end of string
+            n_symbols: alphabet_len + 1,
+            contexts: HashMap::new(),
+        }
+    }
+
+    fn context(&self, history: &[usize]) -> Vec<usize> {
+        // Positions before the start of string are marked with n_symbols
+        let mut context = vec![self.n_symbols; self.order.saturating_sub(history.len())];
+        context.extend_from_slice(&history[history.len().saturating_sub(self.order)..]);
+        context
+    }
+
+    fn freqs(&mut self, context: Vec<usize>) -> &mut Vec<u32> {
+        let n_symbols = self.n_symbols;
+        self.contexts
+            .entry(context)
+            .or_insert_with(|| vec![1; n_symbols])
+    }
+
+    fn update(freqs: &mut [u32], symbol: usize) {
+        freqs[symbol] += INCREMENT;
+        if freqs.iter().sum::<u32>() > MAX_TOTAL {
+            for freq in freqs.iter_mut() {
+                *freq = (*freq / 2).max(1);
+            }
+        }
+    }
+}
+
+pub fn encode_context_strings(strings: &[Vec<usize>], alphabet_len: usize, order: usize) -> Bits {
+    let mut model = ContextModel::new(alphabet_len, order);
+    let mut encoder = RangeEncoder::new();
+    for string in strings {
+        for i in 0..=string.len() {
+            let symbol = string.get(i).copied().unwrap_or(alphabet_len);
+            let context = model.context(&string[..i]);
+            let freqs = model.freqs(context);
+            let start: u32 = freqs[..symbol].iter().sum();
+            let total: u32 = freqs.iter().sum();
+            encoder.encode(start, freqs[symbol], total);
+            ContextModel::update(freqs, symbol);
+        }
+    }
+    encoder.finish()
+}
+
+pub fn decode_context_strings(
+    reader: &mut BitReader,
+    length: usize,
+    alphabet_len: usize,
+    order: usize,
+) -> Result<Vec<Vec<usize>>, DecodeError> {
+    if alphabet_len > MAX_ALPHABET {
+        return Err(DecodeError::InvalidData("alphabet is too large"));
+    }
+    // Every string ends with the end symbol, which is at most as likely as the model allows when
+    // all other symbols have the minimum frequency
+    let n_symbols = alphabet_len as f64 + 1.0;
+    let max_total = MAX_TOTAL as f64;
+    check_length(
+        reader,
+        length,
+        (max_total / (max_total - n_symbols + 1.0)).log2(),
+    )?;
+    let mut model = ContextModel::new(alphabet_len, order);
+    let mut decoder = RangeDecoder::new(reader)?;
+    let mut strings = Vec::new();
+    for _ in 0..length {
+        let mut string = Vec::new();
+        loop {
+            let context = model.context(&string);
+            let freqs = model.freqs(context);
+            let total: u32 = freqs.iter().sum();
+            let value = decoder.decode_freq(total);
+            let mut symbol = 0;
+            let mut start = 0;
+            while start + freqs[symbol] <= value {
+                start += freqs[symbol];
+                symbol += 1;
+            }
+            decoder.decode_update(start, freqs[symbol])?;
+            ContextModel::update(freqs, symbol);
+            if symbol == alphabet_len {
+                break;
+            }
+            string.push(symbol);
+        }
+        strings.push(string);
+    }
+    Ok(strings)
+}
+
+// Codes strings character by character, conditioned on up to MAX_ORDER preceding characters. The
+// best order is picked by trying all of them. The coded data of all strings is stored in the
+// engine, in order.
+pub fn context_coded(objs: &[&String], opts: AutoCompressOpts) -> Option<MultiCompressedData> {
+    let alphabet: Vec<char> = objs
+        .iter()
+        .flat_map(|s| s.chars())
+        .collect::<BTreeSet<char>>()
+        .into_iter()
+        .collect();
+    if alphabet.len() > MAX_ALPHABET {
+        return None;
+    }
+    let index_of: HashMap<char, usize> =
+        alphabet.iter().enumerate().map(|(i, c)| (*c, i)).collect();
+    let strings: Vec<Vec<usize>> = objs
+        .iter()
+        .map(|s| s.chars().map(|c| index_of[&c]).collect())
+        .collect();
+
+    let alphabet_refs: Vec<&char> = alphabet.iter().collect();
+    let alphabet_compressed = autocompress_one(&alphabet_refs, opts);
+
+    let orders: Vec<usize> = (0..=MAX_ORDER).collect();
+    let (order, data) = map(&orders, |order| {
+        (
+            *order,
+            encode_context_strings(&strings, alphabet.len(), *order),
+        )
+    })
+    .into_iter()
+    .min_by_key(|(_, data)| data.len())
+    .unwrap();
+
+    Some(MultiCompressedData {
+        engine: Engine::ContextString {
+            order,
+            length: objs.len(),
+            alphabet_engine: Box::new(alphabet_compressed.engine),
+            alphabet_data: alphabet_compressed.binary_data,
+            data,
+        },
+        binary_data: vec![Bits::new(); objs.len()],
+    })
+}
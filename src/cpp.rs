@@ -0,0 +1,1118 @@
+use crate::decompress::DecodeError;
+use crate::packet::packet_engines;
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+// The generated program mirrors Engine::read and Decoder: reading an engine builds its decoder
+// right away, decoding the embedded objects it needs, e.g. alphabets. Every variant is a struct
+// with a static `read`, and only the variants used in the packet are emitted, along with the
+// shared code they need.
+
+const PRELUDE: &str = r#"// Generated by taco. Prints the files stored in the packet below one after another, or only the
+// i-th of them, counting from 1, if i is passed as the argument. 128-bit integers require GCC or
+// Clang.
+#include <algorithm>
+#include <cctype>
+#include <cstdint>
+#include <cstdio>
+#include <cstdlib>
+#include <deque>
+#include <map>
+#include <set>
+#include <string>
+#include <utility>
+#include <vector>
+
+typedef __int128 i128;
+typedef unsigned __int128 u128;
+
+[[noreturn]] void fail(const char* reason) {
+    fprintf(stderr, "invalid packet: %s\n", reason);
+    exit(1);
+}
+
+// Decoded objects are dynamically typed. Characters are integers, bytes are kept in `s`
+struct Value {
+    enum Kind { INT, STR, BYTES, VEC } kind = INT;
+    i128 n = 0;
+    std::string s;
+    std::vector<Value> v;
+};
+
+inline Value int_value(i128 n) {
+    Value value;
+    value.n = n;
+    return value;
+}
+
+inline Value str_value(std::string s, Value::Kind kind = Value::STR) {
+    Value value;
+    value.kind = kind;
+    value.s = std::move(s);
+    return value;
+}
+
+inline Value vec_value(std::vector<Value> v) {
+    Value value;
+    value.kind = Value::VEC;
+    value.v = std::move(v);
+    return value;
+}
+
+inline i128 as_int(const Value& value) {
+    if (value.kind != Value::INT) fail("expected an integer");
+    return value.n;
+}
+
+inline size_t as_size(const Value& value) {
+    i128 n = as_int(value);
+    if (n < 0 || (u128)n > SIZE_MAX) fail("expected a non-negative integer");
+    return (size_t)n;
+}
+
+inline std::string as_str(Value value) {
+    if (value.kind != Value::STR) fail("expected a string");
+    return std::move(value.s);
+}
+
+inline std::vector<Value> as_vec(Value value) {
+    if (value.kind != Value::VEC) fail("expected a vector");
+    return std::move(value.v);
+}
+
+inline std::string int_to_string(i128 n) {
+    u128 m = n < 0 ? -(u128)n : (u128)n;
+    std::string s;
+    do {
+        s += (char)('0' + (int)(m % 10));
+        m /= 10;
+    } while (m != 0);
+    if (n < 0) s += '-';
+    std::reverse(s.begin(), s.end());
+    return s;
+}
+
+// UTF-8 encoding of a character
+inline std::string utf8(const Value& value) {
+    i128 c = as_int(value);
+    if (c < 0 || c > 0x10ffff || (c >= 0xd800 && c < 0xe000)) fail("expected a character");
+    std::string s;
+    if (c < 0x80) {
+        s += (char)c;
+    } else if (c < 0x800) {
+        s += (char)(0xc0 | c >> 6);
+        s += (char)(0x80 | (c & 0x3f));
+    } else if (c < 0x10000) {
+        s += (char)(0xe0 | c >> 12);
+        s += (char)(0x80 | (c >> 6 & 0x3f));
+        s += (char)(0x80 | (c & 0x3f));
+    } else {
+        s += (char)(0xf0 | c >> 18);
+        s += (char)(0x80 | (c >> 12 & 0x3f));
+        s += (char)(0x80 | (c >> 6 & 0x3f));
+        s += (char)(0x80 | (c & 0x3f));
+    }
+    return s;
+}
+
+// Bits are read starting from the most significant bit of each byte. Fixed-length integers are
+// stored starting from their least significant bit
+struct Reader {
+    const unsigned char* data;
+    size_t length, position;
+
+    bool bit() {
+        if (position >= length) fail("unexpected end of data");
+        bool bit = data[position / 8] >> (7 - position % 8) & 1;
+        position++;
+        return bit;
+    }
+
+    u128 fixint(size_t bit_length) {
+        if (bit_length > 128) fail("integer is too long");
+        u128 num = 0;
+        for (size_t i = 0; i < bit_length; i++) num |= (u128)bit() << i;
+        return num;
+    }
+};
+
+inline u128 varuint(Reader& r) {
+    bool low = r.bit(), high = r.bit();
+    if (!high) return low;
+    if (!low) return 2;
+    u128 n_bits = varuint(r);
+    if (n_bits > 126) fail("varint is too long");
+    n_bits += 2;
+    return (u128)1 << (n_bits - 1) | r.fixint((size_t)n_bits - 1);
+}
+
+inline i128 varint(Reader& r) {
+    bool negative = r.bit();
+    u128 num = varuint(r);
+    if (num >> 127) fail("varint is out of range");
+    return negative ? -(i128)num - 1 : (i128)num;
+}
+
+inline size_t count(Reader& r) {
+    u128 n = varuint(r);
+    if (n > SIZE_MAX) fail("count is out of range");
+    return (size_t)n;
+}
+
+inline size_t read_length(Reader& r) {
+    i128 length = varint(r);
+    if (length < 0 || length > 128) fail("bit length is out of range");
+    return (size_t)length;
+}
+
+inline size_t bit_length(u128 num) {
+    size_t length = 0;
+    for (; num != 0; num >>= 1) length++;
+    return length;
+}
+
+struct Decoder {
+    virtual Value decode(Reader& r) = 0;
+    virtual ~Decoder() {}
+};
+
+// Decoders live until the program exits
+Decoder* read_engine(Reader& r);
+
+// Reads an engine and the object stored right after it, e.g. an alphabet
+inline Value read_embedded(Reader& r) {
+    return read_engine(r)->decode(r);
+}
+"#;
+
+const HUFFMAN: &str = r#"
+struct Huffman : Decoder {
+    std::vector<Value> alphabet;
+    // Symbol index by code length and code
+    std::map<std::pair<size_t, u128>, size_t> codes;
+    size_t max_length = 0;
+
+    Value decode(Reader& r) override {
+        std::pair<size_t, u128> code(0, 0);
+        for (;;) {
+            auto it = codes.find(code);
+            if (it != codes.end()) return alphabet[it->second];
+            if (code.first >= max_length) fail("unknown Huffman code");
+            code = {code.first + 1, code.second * 2 + r.bit()};
+        }
+    }
+};
+"#;
+
+const QUEUE: &str = r#"
+// Objects that are decoded in advance, e.g. stateful lists and range-coded data
+struct Queue : Decoder {
+    std::deque<Value> items;
+
+    Value decode(Reader&) override {
+        if (items.empty()) fail("too many objects requested");
+        Value value = std::move(items.front());
+        items.pop_front();
+        return value;
+    }
+};
+"#;
+
+const RANGE_DECODER: &str = r#"
+// Range coder in the style of LZMA
+struct RangeDecoder {
+    Reader& r;
+    uint32_t code = 0, range = UINT32_MAX, step = 0;
+
+    explicit RangeDecoder(Reader& r) : r(r) {
+        for (int i = 0; i < 4; i++) code = code << 8 | (uint32_t)r.fixint(8);
+    }
+
+    // A value in [0, total) within the frequency range of the encoded symbol
+    uint32_t decode_freq(uint32_t total) {
+        step = range / total;
+        return std::min(code / step, total - 1);
+    }
+
+    void decode_update(uint32_t start, uint32_t size) {
+        if (code < start * step) fail("invalid range-coded data");
+        code -= start * step;
+        range = step * size;
+        while (range < 1u << 24) {
+            range <<= 8;
+            code = code << 8 | (uint32_t)r.fixint(8);
+        }
+    }
+};
+"#;
+
+// Variant name, the tag read_engine dispatches on, with extended engines numbered from 16, and the
+// definition of the variant
+const VARIANTS: &[(&str, u32, &str)] = &[
+    (
+        "VarInt",
+        0,
+        r#"
+struct VarInt : Decoder {
+    static Decoder* read(Reader&) {
+        return new VarInt();
+    }
+
+    Value decode(Reader& r) override {
+        return int_value(varint(r));
+    }
+};
+"#,
+    ),
+    (
+        "FixedInt",
+        1,
+        r#"
+struct FixedInt : Decoder {
+    i128 bias;
+    size_t length;
+
+    static Decoder* read(Reader& r) {
+        FixedInt* d = new FixedInt();
+        d->bias = varint(r);
+        d->length = read_length(r);
+        return d;
+    }
+
+    Value decode(Reader& r) override {
+        return int_value((i128)((u128)bias + r.fixint(length)));
+    }
+};
+"#,
+    ),
+    (
+        "SpecificHuffman",
+        2,
+        r#"
+struct SpecificHuffman {
+    static void walk(Reader& r, Huffman* d, size_t symbol_length, size_t length, u128 code) {
+        if (r.bit()) {
+            size_t i = (size_t)r.fixint(symbol_length);
+            if (i >= d->alphabet.size()) fail("Huffman symbol is out of range");
+            d->codes[{length, code}] = i;
+            d->max_length = std::max(d->max_length, length);
+        } else {
+            if (length >= 128) fail("Huffman tree is too deep");
+            walk(r, d, symbol_length, length + 1, code * 2);
+            walk(r, d, symbol_length, length + 1, code * 2 + 1);
+        }
+    }
+
+    static Decoder* read(Reader& r) {
+        Huffman* d = new Huffman();
+        d->alphabet = as_vec(read_embedded(r));
+        walk(r, d, bit_length(d->alphabet.size()), 0, 0);
+        return d;
+    }
+};
+"#,
+    ),
+    (
+        "CanonicalHuffman",
+        3,
+        r#"
+struct CanonicalHuffman {
+    static Decoder* read(Reader& r) {
+        Huffman* d = new Huffman();
+        d->alphabet = as_vec(read_embedded(r));
+        std::vector<Value> lengths = as_vec(read_embedded(r));
+        if (lengths.size() != d->alphabet.size()) {
+            fail("Huffman alphabet and code lengths mismatch");
+        }
+        // Codes are assigned in order of increasing length
+        u128 code = 0;
+        size_t code_length = 0;
+        for (size_t i = 0; i < lengths.size(); i++) {
+            size_t length = as_size(lengths[i]);
+            if (length < code_length || length > 128) fail("invalid Huffman code lengths");
+            if (i > 0) code++;
+            code = length - code_length < 128 ? code << (length - code_length) : 0;
+            code_length = length;
+            if (length < 128 && code >> length != 0) fail("invalid Huffman code lengths");
+            d->codes[{length, code}] = i;
+        }
+        d->max_length = code_length;
+        return d;
+    }
+};
+"#,
+    ),
+    (
+        "String",
+        4,
+        r#"
+struct String : Decoder {
+    Decoder* chars;
+
+    static Decoder* read(Reader& r) {
+        String* d = new String();
+        d->chars = read_engine(r);
+        return d;
+    }
+
+    Value decode(Reader& r) override {
+        std::string s;
+        for (const Value& c : as_vec(chars->decode(r))) s += utf8(c);
+        return str_value(s);
+    }
+};
+"#,
+    ),
+    (
+        "StringConcat",
+        5,
+        r#"
+struct StringConcat : Decoder {
+    std::string separator;
+    Decoder* words;
+
+    static Decoder* read(Reader& r) {
+        StringConcat* d = new StringConcat();
+        d->separator = utf8(int_value(varint(r)));
+        d->words = read_engine(r);
+        return d;
+    }
+
+    Value decode(Reader& r) override {
+        std::vector<Value> items = as_vec(words->decode(r));
+        std::string s;
+        for (size_t i = 0; i < items.size(); i++) {
+            if (i > 0) s += separator;
+            s += as_str(std::move(items[i]));
+        }
+        return str_value(s);
+    }
+};
+"#,
+    ),
+    (
+        "IntSet",
+        6,
+        r#"
+struct IntSet : Decoder {
+    bool unique;
+    Decoder *length, *min, *max;
+
+    static Decoder* read(Reader& r) {
+        IntSet* d = new IntSet();
+        d->unique = r.bit();
+        d->length = read_engine(r);
+        d->min = read_engine(r);
+        d->max = read_engine(r);
+        return d;
+    }
+
+    // The middle number is stored first, then both halves with narrowed bounds
+    static void slice(Reader& r, size_t n, i128 lo, i128 hi, bool unique, std::vector<Value>& nums) {
+        if (n == 0) return;
+        size_t m = n / 2;
+        if (unique) {
+            i128 m_lo = lo + (i128)m, m_hi = hi - (i128)(n - m - 1);
+            if (m_lo > m_hi) fail("invalid integer set bounds");
+            i128 mid = m_lo + (i128)r.fixint(bit_length((u128)(m_hi - m_lo)));
+            slice(r, m, lo, mid - 1, true, nums);
+            nums.push_back(int_value(mid));
+            slice(r, n - m - 1, mid + 1, hi, true, nums);
+        } else {
+            if (lo > hi) fail("invalid integer set bounds");
+            i128 mid = lo + (i128)r.fixint(bit_length((u128)(hi - lo)));
+            slice(r, m, lo, mid, false, nums);
+            nums.push_back(int_value(mid));
+            slice(r, n - m - 1, mid, hi, false, nums);
+        }
+    }
+
+    Value decode(Reader& r) override {
+        size_t n = as_size(length->decode(r));
+        i128 lo = as_int(min->decode(r));
+        i128 hi = as_int(max->decode(r));
+        std::vector<Value> nums;
+        if (unique) {
+            if (n < 2) fail("unique set is too short");
+            nums.push_back(int_value(lo));
+            slice(r, n - 2, lo + 1, hi - 1, true, nums);
+            nums.push_back(int_value(hi));
+        } else {
+            slice(r, n, lo, hi, false, nums);
+        }
+        return vec_value(nums);
+    }
+};
+"#,
+    ),
+    (
+        "Stateful",
+        7,
+        r#"
+struct Stateful {
+    static Decoder* read(Reader& r) {
+        Queue* d = new Queue();
+        for (Value& item : as_vec(read_embedded(r))) d->items.push_back(std::move(item));
+        return d;
+    }
+};
+"#,
+    ),
+    (
+        "Vec",
+        8,
+        r#"
+struct Vec : Decoder {
+    Decoder *length, *item;
+
+    static Decoder* read(Reader& r) {
+        Vec* d = new Vec();
+        d->length = read_engine(r);
+        d->item = read_engine(r);
+        return d;
+    }
+
+    Value decode(Reader& r) override {
+        size_t n = as_size(length->decode(r));
+        std::vector<Value> items;
+        for (size_t i = 0; i < n; i++) items.push_back(item->decode(r));
+        return vec_value(items);
+    }
+};
+"#,
+    ),
+    (
+        "VecRLE",
+        9,
+        r#"
+struct VecRLE : Decoder {
+    Decoder *length, *item;
+
+    static Decoder* read(Reader& r) {
+        VecRLE* d = new VecRLE();
+        d->length = read_engine(r);
+        d->item = read_engine(r);
+        return d;
+    }
+
+    Value decode(Reader& r) override {
+        std::vector<Value> run_lengths = as_vec(length->decode(r));
+        std::vector<Value> run_values = as_vec(item->decode(r));
+        if (run_lengths.size() != run_values.size()) fail("run lengths and values mismatch");
+        std::vector<Value> items;
+        for (size_t i = 0; i < run_lengths.size(); i++) {
+            for (size_t j = as_size(run_lengths[i]); j > 0; j--) items.push_back(run_values[i]);
+        }
+        return vec_value(items);
+    }
+};
+"#,
+    ),
+    (
+        "CategorySplit",
+        10,
+        r#"
+struct CategorySplit : Decoder {
+    std::vector<Decoder*> categories;
+    Decoder* category;
+
+    static Decoder* read(Reader& r) {
+        CategorySplit* d = new CategorySplit();
+        i128 n = varint(r);
+        if (n < 0) fail("negative category count");
+        for (i128 i = 0; i < n; i++) d->categories.push_back(read_engine(r));
+        d->category = read_engine(r);
+        return d;
+    }
+
+    Value decode(Reader& r) override {
+        size_t i = as_size(category->decode(r));
+        if (i >= categories.size()) fail("category is out of range");
+        return categories[i]->decode(r);
+    }
+};
+"#,
+    ),
+    (
+        "Constant",
+        12,
+        r#"
+struct Constant : Decoder {
+    Value value;
+
+    static Decoder* read(Reader& r) {
+        Constant* d = new Constant();
+        d->value = read_embedded(r);
+        return d;
+    }
+
+    Value decode(Reader&) override {
+        return value;
+    }
+};
+"#,
+    ),
+    (
+        "Alphabet",
+        13,
+        r#"
+struct Alphabet : Decoder {
+    std::vector<Value> alphabet;
+    Decoder* index;
+
+    static Decoder* read(Reader& r) {
+        Alphabet* d = new Alphabet();
+        d->alphabet = as_vec(read_embedded(r));
+        d->index = read_engine(r);
+        return d;
+    }
+
+    Value decode(Reader& r) override {
+        size_t i = as_size(index->decode(r));
+        if (i >= alphabet.size()) fail("alphabet index is out of range");
+        return alphabet[i];
+    }
+};
+"#,
+    ),
+    (
+        "StringifiedInt",
+        14,
+        r#"
+struct StringifiedInt : Decoder {
+    Decoder* inner;
+
+    static Decoder* read(Reader& r) {
+        StringifiedInt* d = new StringifiedInt();
+        d->inner = read_engine(r);
+        return d;
+    }
+
+    Value decode(Reader& r) override {
+        return str_value(int_to_string(as_int(inner->decode(r))));
+    }
+};
+"#,
+    ),
+    (
+        "StringifiedDecimal",
+        15,
+        r#"
+struct StringifiedDecimal : Decoder {
+    Decoder *inner, *precision;
+
+    static Decoder* read(Reader& r) {
+        StringifiedDecimal* d = new StringifiedDecimal();
+        d->inner = read_engine(r);
+        d->precision = read_engine(r);
+        return d;
+    }
+
+    Value decode(Reader& r) override {
+        std::string s = int_to_string(as_int(inner->decode(r)));
+        size_t digits = as_size(precision->decode(r));
+        if (digits > s.size()) fail("precision is out of range");
+        s.insert(s.size() - digits, ".");
+        return str_value(s);
+    }
+};
+"#,
+    ),
+    (
+        "Bytes",
+        16,
+        r#"
+struct Bytes : Decoder {
+    Decoder* inner;
+
+    static Decoder* read(Reader& r) {
+        Bytes* d = new Bytes();
+        d->inner = read_engine(r);
+        return d;
+    }
+
+    Value decode(Reader& r) override {
+        std::string s;
+        for (const Value& byte : as_vec(inner->decode(r))) {
+            i128 n = as_int(byte);
+            if (n < 0 || n > 255) fail("expected a byte");
+            s += (char)n;
+        }
+        return str_value(s, Value::BYTES);
+    }
+};
+"#,
+    ),
+    (
+        "RangeCoded",
+        17,
+        r#"
+struct RangeCoded {
+    static Decoder* read(Reader& r) {
+        size_t length = count(r);
+        std::vector<Value> alphabet = as_vec(read_embedded(r));
+        std::vector<Value> freqs = as_vec(read_embedded(r));
+        if (freqs.size() != alphabet.size()) fail("alphabet and symbol frequencies mismatch");
+        std::vector<uint32_t> cumulative(1, 0);
+        for (const Value& freq : freqs) {
+            i128 n = as_int(freq);
+            if (n <= 0 || cumulative.back() + n > 1 << 16) fail("invalid symbol frequencies");
+            cumulative.push_back(cumulative.back() + (uint32_t)n);
+        }
+        if (alphabet.empty()) fail("invalid symbol frequencies");
+
+        Queue* d = new Queue();
+        RangeDecoder decoder(r);
+        for (size_t i = 0; i < length; i++) {
+            uint32_t value = decoder.decode_freq(cumulative.back());
+            size_t symbol =
+                std::upper_bound(cumulative.begin(), cumulative.end(), value) - cumulative.begin() - 1;
+            decoder.decode_update(cumulative[symbol], cumulative[symbol + 1] - cumulative[symbol]);
+            d->items.push_back(alphabet[symbol]);
+        }
+        return d;
+    }
+};
+"#,
+    ),
+    (
+        "RangeCodedInt",
+        18,
+        r#"
+struct RangeCodedInt {
+    static Decoder* read(Reader& r) {
+        i128 bias = varint(r);
+        u128 size = varuint(r);
+        size_t length = count(r);
+        if (size == 0) fail("empty integer range");
+        // Integers in [0, size) are split into a top digit and 16-bit low digits
+        size_t n_low_digits = 0;
+        while ((size - 1) >> (16 * n_low_digits) >= 1 << 16) n_low_digits++;
+        uint32_t top_total = (uint32_t)((size - 1) >> (16 * n_low_digits)) + 1;
+
+        Queue* d = new Queue();
+        RangeDecoder decoder(r);
+        for (size_t i = 0; i < length; i++) {
+            u128 num = decoder.decode_freq(top_total);
+            decoder.decode_update((uint32_t)num, 1);
+            for (size_t j = 0; j < n_low_digits; j++) {
+                uint32_t digit = decoder.decode_freq(1 << 16);
+                decoder.decode_update(digit, 1);
+                num = num << 16 | digit;
+            }
+            if (num >= size) fail("range-coded integer is out of range");
+            d->items.push_back(int_value((i128)((u128)bias + num)));
+        }
+        return d;
+    }
+};
+"#,
+    ),
+    (
+        "ContextString",
+        19,
+        r#"
+struct ContextString {
+    static Decoder* read(Reader& r) {
+        size_t order = count(r);
+        if (order > 2) fail("context order is out of range");
+        size_t length = count(r);
+        std::vector<std::string> alphabet;
+        for (const Value& c : as_vec(read_embedded(r))) alphabet.push_back(utf8(c));
+        if (alphabet.size() > 256) fail("alphabet is too large");
+
+        // Adaptive order-k model: each symbol is coded with frequencies of the symbols that
+        // followed the same k symbols before. The last symbol ends a string, and positions
+        // before the start of a string are marked with n_symbols
+        size_t n_symbols = alphabet.size() + 1;
+        std::map<std::vector<size_t>, std::vector<uint32_t>> contexts;
+        Queue* d = new Queue();
+        RangeDecoder decoder(r);
+        for (size_t i = 0; i < length; i++) {
+            std::vector<size_t> history;
+            std::string s;
+            for (;;) {
+                std::vector<size_t> context(order - std::min(order, history.size()), n_symbols);
+                context.insert(context.end(), history.end() - std::min(order, history.size()),
+                               history.end());
+                auto it = contexts.find(context);
+                if (it == contexts.end()) {
+                    it = contexts.emplace(context, std::vector<uint32_t>(n_symbols, 1)).first;
+                }
+                std::vector<uint32_t>& freqs = it->second;
+                uint32_t total = 0;
+                for (uint32_t freq : freqs) total += freq;
+                uint32_t value = decoder.decode_freq(total);
+                size_t symbol = 0;
+                uint32_t start = 0;
+                while (start + freqs[symbol] <= value) start += freqs[symbol++];
+                decoder.decode_update(start, freqs[symbol]);
+                freqs[symbol] += 32;
+                if (total + 32 > 1 << 16) {
+                    for (uint32_t& freq : freqs) freq = std::max(freq / 2, 1u);
+                }
+                if (symbol == alphabet.size()) break;
+                history.push_back(symbol);
+                s += alphabet[symbol];
+            }
+            d->items.push_back(str_value(s));
+        }
+        return d;
+    }
+};
+"#,
+    ),
+    (
+        "Schema",
+        20,
+        r#"
+// Counts are either numbers or names of integers read before
+struct Count {
+    std::string name;
+    size_t n = 0;
+};
+
+struct Field {
+    bool is_int;
+    bool repeated = false;
+    Count repeat;
+    std::string name;
+    size_t slot;
+};
+
+struct Item {
+    bool is_line;
+    std::vector<Field> fields;
+    Count count;
+    std::vector<Item> items;
+};
+
+// Parses the normalized schema stored in the packet, which is known to be valid
+struct SchemaParser {
+    std::vector<std::string> tokens;
+    size_t position = 0;
+    std::set<std::string> names;
+    size_t n_slots = 0;
+
+    explicit SchemaParser(const std::string& text) {
+        for (size_t i = 0; i < text.size();) {
+            if (text[i] == ' ') {
+                i++;
+            } else if (std::string("/()[],").find(text[i]) != std::string::npos) {
+                tokens.push_back(text.substr(i++, 1));
+            } else {
+                size_t j = i + 1;
+                while (j < text.size() && (isalnum(text[j]) || text[j] == '_')) j++;
+                tokens.push_back(text.substr(i, j - i));
+                i = j;
+            }
+        }
+    }
+
+    std::string peek(size_t offset) {
+        return position + offset < tokens.size() ? tokens[position + offset] : "";
+    }
+
+    std::string next() {
+        if (position >= tokens.size()) fail("invalid schema");
+        return tokens[position++];
+    }
+
+    // Integers can be written in scientific notation, e.g. 2e5
+    static bool number(const std::string& s, i128& n) {
+        size_t e = s.find('e');
+        std::string mantissa = s.substr(0, e);
+        std::string exponent = e == std::string::npos ? "0" : s.substr(e + 1);
+        bool negative = !mantissa.empty() && mantissa[0] == '-';
+        if (negative) mantissa.erase(0, 1);
+        if (mantissa.empty() || exponent.empty()) return false;
+        n = 0;
+        for (char c : mantissa + exponent) {
+            if (!isdigit(c)) return false;
+        }
+        for (char c : mantissa) n = n * 10 + (c - '0');
+        for (int i = atoi(exponent.c_str()); i > 0; i--) n *= 10;
+        if (negative) n = -n;
+        return true;
+    }
+
+    bool is_count(const std::string& token) {
+        i128 n;
+        return (number(token, n) && n >= 0) || names.count(token);
+    }
+
+    Count count() {
+        std::string token = next();
+        Count count;
+        i128 n;
+        if (number(token, n)) {
+            count.n = (size_t)n;
+        } else {
+            count.name = token;
+        }
+        return count;
+    }
+
+    // Ranges don't affect decoding
+    void skip_range() {
+        if (peek(0) == "in") position += 6;
+    }
+
+    Field field(bool is_int) {
+        Field field;
+        field.is_int = is_int;
+        field.slot = n_slots++;
+        return field;
+    }
+
+    std::vector<Item> block() {
+        std::vector<Item> items = {item()};
+        while (peek(0) == "/") {
+            next();
+            items.push_back(item());
+        }
+        return items;
+    }
+
+    Item item() {
+        Item item;
+        item.is_line = false;
+        std::string token = peek(0), kind = peek(1);
+        if (is_count(token) && kind == "x") {
+            item.count = count();
+            next();
+            if (peek(0) == "(") {
+                next();
+                item.items = block();
+                next();
+            } else {
+                item.items.push_back(Item{true, line(), Count(), {}});
+            }
+        } else if (is_count(token) && (kind == "pairs" || kind == "triples")) {
+            item.count = count();
+            next();
+            skip_range();
+            std::vector<Field> fields;
+            for (int i = kind == "pairs" ? 2 : 3; i > 0; i--) fields.push_back(field(true));
+            item.items.push_back(Item{true, fields, Count(), {}});
+        } else {
+            item.is_line = true;
+            item.fields = line();
+        }
+        return item;
+    }
+
+    std::vector<Field> line() {
+        std::vector<Field> fields;
+        while (position < tokens.size() && peek(0) != "/" && peek(0) != ")") {
+            std::string token = peek(0);
+            Field f;
+            if (token == "int" || token == "str") {
+                next();
+                f = field(token == "int");
+            } else if (peek(1) == "ints" || peek(1) == "strs") {
+                Count repeat = count();
+                f = field(next() == "ints");
+                f.repeated = true;
+                f.repeat = repeat;
+            } else {
+                next();
+                names.insert(token);
+                f = field(true);
+                f.name = token;
+            }
+            if (f.is_int) skip_range();
+            fields.push_back(f);
+        }
+        return fields;
+    }
+};
+
+struct Schema : Decoder {
+    std::vector<Item> items;
+    Decoder* newline;
+    std::vector<Decoder*> fields;
+
+    static Decoder* read(Reader& r) {
+        Schema* d = new Schema();
+        size_t length = count(r);
+        std::string text;
+        for (size_t i = 0; i < length; i++) text += (char)r.fixint(7);
+        SchemaParser parser(text);
+        d->items = parser.block();
+        d->newline = read_engine(r);
+        for (size_t i = 0; i < parser.n_slots; i++) d->fields.push_back(read_engine(r));
+        return d;
+    }
+
+    static size_t resolve(const Count& count, const std::map<std::string, i128>& vars) {
+        if (count.name.empty()) return count.n;
+        auto it = vars.find(count.name);
+        if (it == vars.end() || it->second < 0 || (u128)it->second > SIZE_MAX) {
+            fail("schema count is out of range");
+        }
+        return (size_t)it->second;
+    }
+
+    void generate(Reader& r, const std::vector<Item>& items, std::map<std::string, i128>& vars,
+                  std::vector<std::string>& lines) {
+        for (const Item& item : items) {
+            if (!item.is_line) {
+                for (size_t i = resolve(item.count, vars); i > 0; i--) {
+                    generate(r, item.items, vars, lines);
+                }
+                continue;
+            }
+            std::string line;
+            bool first = true;
+            for (const Field& field : item.fields) {
+                size_t n = field.repeated ? resolve(field.repeat, vars) : 1;
+                for (size_t i = 0; i < n; i++) {
+                    Value value = fields[field.slot]->decode(r);
+                    if (!first) line += ' ';
+                    first = false;
+                    if (field.is_int) {
+                        if (!field.name.empty()) vars[field.name] = as_int(value);
+                        line += int_to_string(as_int(value));
+                    } else {
+                        line += as_str(value);
+                    }
+                }
+            }
+            lines.push_back(line);
+        }
+    }
+
+    Value decode(Reader& r) override {
+        bool has_newline = as_size(newline->decode(r)) != 0;
+        std::map<std::string, i128> vars;
+        std::vector<std::string> lines;
+        generate(r, items, vars, lines);
+        std::string s;
+        for (size_t i = 0; i < lines.size(); i++) {
+            if (i > 0) s += '\n';
+            s += lines[i];
+        }
+        if (has_newline) s += '\n';
+        return str_value(s);
+    }
+};
+"#,
+    ),
+    (
+        "Stored",
+        22,
+        r#"
+struct Stored : Decoder {
+    static Decoder* read(Reader&) {
+        return new Stored();
+    }
+
+    Value decode(Reader& r) override {
+        Value::Kind kind = r.bit() ? Value::STR : Value::BYTES;
+        size_t length = count(r);
+        std::string s;
+        for (size_t i = 0; i < length; i++) s += (char)r.fixint(8);
+        return str_value(s, kind);
+    }
+};
+"#,
+    ),
+];
+
+const MAIN: &str = r#"
+int main(int argc, char** argv) {
+    // The header is the magic bytes, the format version and the payload length in bits
+    uint64_t payload_bits = 0;
+    for (int i = 12; i >= 5; i--) payload_bits = payload_bits << 8 | PACKET[i];
+    Reader r{PACKET + 13, (size_t)payload_bits, 0};
+
+    size_t n_files = count(r);
+    size_t only = 0;
+    if (argc > 1) {
+        only = strtoull(argv[1], nullptr, 10);
+        if (only == 0 || only > n_files) {
+            fprintf(stderr, "file number must be between 1 and %zu\n", n_files);
+            return 1;
+        }
+    }
+    bool has_names = r.bit();
+    // Answers are stored after the files and are not printed
+    r.bit();
+    if (has_names) {
+        // File names are not printed, but have to be decoded to reach the files
+        Decoder* names = read_engine(r);
+        for (size_t i = 0; i < n_files; i++) names->decode(r);
+    }
+    Decoder* files = read_engine(r);
+    for (size_t i = 1; i <= n_files; i++) {
+        Value file = files->decode(r);
+        if (file.kind != Value::STR && file.kind != Value::BYTES) fail("expected file contents");
+        if (only == 0 || only == i) fwrite(file.s.data(), 1, file.s.size(), stdout);
+    }
+    return 0;
+}
+"#;
+
+const BYTES_PER_LINE: usize = 24;
+
+/// Generates a standalone C++ program that prints the files of a packet, which is embedded in it
+/// as a byte array. The program only contains the decoders of the engines the packet uses, so that
+/// a large test can be shipped as a small generator.
+pub fn packet_to_cpp(packet: &[u8]) -> Result<String, DecodeError> {
+    let mut variants = BTreeSet::new();
+    for engine in packet_engines(packet)? {
+        engine.collect_variants(&mut variants);
+    }
+    let uses = |names: &[&str]| names.iter().any(|name| variants.contains(name));
+
+    let mut out = String::from(PRELUDE);
+    if uses(&["SpecificHuffman", "CanonicalHuffman"]) {
+        out.push_str(HUFFMAN);
+    }
+    if uses(&["Stateful", "RangeCoded", "RangeCodedInt", "ContextString"]) {
+        out.push_str(QUEUE);
+    }
+    if uses(&["RangeCoded", "RangeCodedInt", "ContextString"]) {
+        out.push_str(RANGE_DECODER);
+    }
+    let used: Vec<_> = VARIANTS
+        .iter()
+        .filter(|(name, ..)| variants.contains(name))
+        .collect();
+    for (_, _, code) in &used {
+        out.push_str(code);
+    }
+
+    out.push_str(
+        "
+Decoder* read_engine(Reader& r) {
+    int tag = 0;
+    for (int i = 0; i < 4; i++) tag = tag * 2 + r.bit();
+    // Extended engines are numbered from 16
+    if (tag == 11) {
+        u128 k = varuint(r);
+        tag = k < 16 ? 16 + (int)k : -1;
+    }
+    switch (tag) {
+",
+    );
+    for (name, tag, _) in &used {
+        writeln!(out, "    case {tag}: return {name}::read(r);").unwrap();
+    }
+    out.push_str(
+        "    }
+    fail(\"engine is not supported by this decoder\");
+}
+
+static const unsigned char PACKET[] = {
+",
+    );
+    for line in packet.chunks(BYTES_PER_LINE) {
+        let bytes: Vec<String> = line.iter().map(|byte| byte.to_string()).collect();
+        writeln!(out, "    {},", bytes.join(", ")).unwrap();
+    }
+    out.push_str("};\n");
+    out.push_str(MAIN);
+    Ok(out)
+}
@@ -0,0 +1,969 @@
+use crate::answers::line_count;
+use crate::bits::{BitReader, Bits};
+use crate::compress::Engine;
+use crate::context_model::{decode_context_strings, MAX_ORDER};
+use crate::range_coding::{decode_range_coded, decode_range_coded_ints};
+use crate::schema::Schema;
+use crate::varint::{decompress_fixint, decompress_varint, decompress_varuint, get_bit_length};
+use std::collections::{HashMap, VecDeque};
+use std::fmt;
+
+/// An error encountered while decoding corrupted or unsupported data.
+#[derive(Debug)]
+pub enum DecodeError {
+    UnexpectedEnd,
+    InvalidData(&'static str),
+    InvalidMagic,
+    UnsupportedVersion(u8),
+    TrailingData,
+    ChecksumMismatch { file: usize },
+}
+
+impl fmt::Display for DecodeError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Self::UnexpectedEnd => write!(f, "unexpected end of data"),
+            Self::InvalidData(reason) => write!(f, "invalid data: {reason}"),
+            Self::InvalidMagic => write!(f, "not a taco packet"),
+            Self::UnsupportedVersion(version) => {
+                write!(f, "unsupported packet format version {version}")
+            }
+            Self::TrailingData => write!(f, "unexpected data after the end of packet"),
+            Self::ChecksumMismatch { file } => write!(f, "checksum mismatch in file #{file}"),
+        }
+    }
+}
+
+impl std::error::Error for DecodeError {}
+
+/// A decoded object.
+///
+/// Engines don't record the type they were built for, so decoded objects are dynamically typed.
+/// Characters and all integer types are represented as `Int`.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub enum Value {
+    Int(i128),
+    Str(String),
+    Bytes(Vec<u8>),
+    Vec(Vec<Value>),
+}
+
+impl Value {
+    pub fn into_int(self) -> Result<i128, DecodeError> {
+        match self {
+            Self::Int(n) => Ok(n),
+            _ => Err(DecodeError::InvalidData("expected an integer")),
+        }
+    }
+
+    pub fn into_usize(self) -> Result<usize, DecodeError> {
+        usize::try_from(self.into_int()?)
+            .map_err(|_| DecodeError::InvalidData("expected a non-negative integer"))
+    }
+
+    pub fn into_char(self) -> Result<char, DecodeError> {
+        u32::try_from(self.into_int()?)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or(DecodeError::InvalidData("expected a character"))
+    }
+
+    pub fn into_string(self) -> Result<String, DecodeError> {
+        match self {
+            Self::Str(s) => Ok(s),
+            _ => Err(DecodeError::InvalidData("expected a string")),
+        }
+    }
+
+    pub fn into_vec(self) -> Result<Vec<Value>, DecodeError> {
+        match self {
+            Self::Vec(vec) => Ok(vec),
+            _ => Err(DecodeError::InvalidData("expected a vector")),
+        }
+    }
+}
+
+fn read_length(reader: &mut BitReader) -> Result<usize, DecodeError> {
+    let length = decompress_varint(reader)?;
+    if !(0..=128).contains(&length) {
+        return Err(DecodeError::InvalidData("bit length is out of range"));
+    }
+    Ok(length as usize)
+}
+
+fn read_count(reader: &mut BitReader) -> Result<usize, DecodeError> {
+    usize::try_from(decompress_varuint(reader)?)
+        .map_err(|_| DecodeError::InvalidData("count is out of range"))
+}
+
+fn read_order(reader: &mut BitReader) -> Result<usize, DecodeError> {
+    let order = read_count(reader)?;
+    if order > MAX_ORDER {
+        return Err(DecodeError::InvalidData("context order is out of range"));
+    }
+    Ok(order)
+}
+
+fn decode_freqs(freqs: Value) -> Result<Vec<u32>, DecodeError> {
+    freqs
+        .into_vec()?
+        .into_iter()
+        .map(|freq| {
+            u32::try_from(freq.into_int()?)
+                .map_err(|_| DecodeError::InvalidData("symbol frequency is out of range"))
+        })
+        .collect()
+}
+
+fn decode_one(engine: &Engine, reader: &mut BitReader) -> Result<Value, DecodeError> {
+    Decoder::new(engine)?.decode(reader)
+}
+
+fn read_tree(reader: &mut BitReader, bit_length: usize) -> Result<(), DecodeError> {
+    if !reader.read()? {
+        read_tree(reader, bit_length)?;
+        read_tree(reader, bit_length)?;
+    } else {
+        decompress_fixint(reader, bit_length)?;
+    }
+    Ok(())
+}
+
+impl Engine {
+    pub fn read(reader: &mut BitReader) -> Result<Self, DecodeError> {
+        let mut tag = 0;
+        for _ in 0..4 {
+            tag = tag * 2 + reader.read()? as u8;
+        }
+
+        Ok(match tag {
+            0b0000 => Self::VarInt,
+            0b0001 => Self::FixedInt {
+                bias: decompress_varint(reader)?,
+                length: read_length(reader)?,
+            },
+            0b0010 => {
+                let alphabet_engine = Box::new(Self::read(reader)?);
+                let start = reader.position();
+                let alphabet = decode_one(&alphabet_engine, reader)?.into_vec()?;
+                let alphabet_data = reader.consumed_since(start);
+                let start = reader.position();
+                read_tree(reader, get_bit_length(alphabet.len() as u128))?;
+                let tree = reader.consumed_since(start);
+                Self::SpecificHuffman {
+                    alphabet_engine,
+                    alphabet_data,
+                    tree,
+                }
+            }
+            0b0011 => {
+                let alphabet_engine = Box::new(Self::read(reader)?);
+                let start = reader.position();
+                decode_one(&alphabet_engine, reader)?;
+                let alphabet_data = reader.consumed_since(start);
+                let lengths_engine = Box::new(Self::read(reader)?);
+                let start = reader.position();
+                decode_one(&lengths_engine, reader)?;
+                let lengths_data = reader.consumed_since(start);
+                Self::CanonicalHuffman {
+                    alphabet_engine,
+                    alphabet_data,
+                    lengths_engine,
+                    lengths_data,
+                }
+            }
+            0b0100 => Self::String {
+                chars: Box::new(Self::read(reader)?),
+            },
+            0b0101 => {
+                let separator = Value::Int(decompress_varint(reader)?).into_char()?;
+                Self::StringConcat {
+                    words: Box::new(Self::read(reader)?),
+                    separator,
+                }
+            }
+            0b0110 => {
+                let unique = reader.read()?;
+                Self::IntSet {
+                    length: Box::new(Self::read(reader)?),
+                    min: Box::new(Self::read(reader)?),
+                    max: Box::new(Self::read(reader)?),
+                    unique,
+                }
+            }
+            0b0111 => {
+                let inner = Box::new(Self::read(reader)?);
+                let start = reader.position();
+                decode_one(&inner, reader)?;
+                Self::Stateful {
+                    inner,
+                    data: reader.consumed_since(start),
+                }
+            }
+            0b1000 => Self::Vec {
+                length: Box::new(Self::read(reader)?),
+                item: Box::new(Self::read(reader)?),
+            },
+            0b1001 => Self::VecRLE {
+                length: Box::new(Self::read(reader)?),
+                item: Box::new(Self::read(reader)?),
+            },
+            0b1010 => {
+                let n_categories = decompress_varint(reader)?;
+                if n_categories < 0 {
+                    return Err(DecodeError::InvalidData("negative category count"));
+                }
+                let mut categories = Vec::new();
+                for _ in 0..n_categories {
+                    categories.push(Self::read(reader)?);
+                }
+                Self::CategorySplit {
+                    categories,
+                    category: Box::new(Self::read(reader)?),
+                }
+            }
+            0b1011 => match decompress_varuint(reader)? {
+                0 => Self::Bytes {
+                    inner: Box::new(Self::read(reader)?),
+                },
+                1 => {
+                    let length = read_count(reader)?;
+                    let alphabet_engine = Box::new(Self::read(reader)?);
+                    let start = reader.position();
+                    decode_one(&alphabet_engine, reader)?;
+                    let alphabet_data = reader.consumed_since(start);
+                    let freqs_engine = Box::new(Self::read(reader)?);
+                    let start = reader.position();
+                    let freqs = decode_freqs(decode_one(&freqs_engine, reader)?)?;
+                    let freqs_data = reader.consumed_since(start);
+                    let start = reader.position();
+                    decode_range_coded(reader, length, &freqs)?;
+                    Self::RangeCoded {
+                        length,
+                        alphabet_engine,
+                        alphabet_data,
+                        freqs_engine,
+                        freqs_data,
+                        data: reader.consumed_since(start),
+                    }
+                }
+                2 => {
+                    let bias = decompress_varint(reader)?;
+                    let size = decompress_varuint(reader)?;
+                    let length = read_count(reader)?;
+                    let start = reader.position();
+                    decode_range_coded_ints(reader, length, size)?;
+                    Self::RangeCodedInt {
+                        bias,
+                        size,
+                        length,
+                        data: reader.consumed_since(start),
+                    }
+                }
+                3 => {
+                    let order = read_order(reader)?;
+                    let length = read_count(reader)?;
+                    let alphabet_engine = Box::new(Self::read(reader)?);
+                    let start = reader.position();
+                    let alphabet_len = decode_one(&alphabet_engine, reader)?.into_vec()?.len();
+                    let alphabet_data = reader.consumed_since(start);
+                    let start = reader.position();
+                    decode_context_strings(reader, length, alphabet_len, order)?;
+                    Self::ContextString {
+                        order,
+                        length,
+                        alphabet_engine,
+                        alphabet_data,
+                        data: reader.consumed_since(start),
+                    }
+                }
+                4 => {
+                    let schema = Schema::read(reader)?;
+                    let newline = Box::new(Self::read(reader)?);
+                    let mut fields = Vec::new();
+                    for _ in 0..schema.n_slots() {
+                        fields.push(Self::read(reader)?);
+                    }
+                    Self::Schema {
+                        schema,
+                        newline,
+                        fields,
+                    }
+                }
+                5 => Self::AnswerLines {
+                    token: read_count(reader)?,
+                    offset: decompress_varint(reader)?,
+                    newline: Box::new(Self::read(reader)?),
+                    line: Box::new(Self::read(reader)?),
+                },
+                6 => Self::Stored,
+                _ => return Err(DecodeError::InvalidData("unknown engine")),
+            },
+            0b1100 => {
+                let engine = Box::new(Self::read(reader)?);
+                let start = reader.position();
+                decode_one(&engine, reader)?;
+                Self::Constant {
+                    engine,
+                    data: reader.consumed_since(start),
+                }
+            }
+            0b1101 => {
+                let alphabet_engine = Box::new(Self::read(reader)?);
+                let start = reader.position();
+                decode_one(&alphabet_engine, reader)?;
+                Self::Alphabet {
+                    alphabet_engine,
+                    alphabet_data: reader.consumed_since(start),
+                    index: Box::new(Self::read(reader)?),
+                }
+            }
+            0b1110 => Self::StringifiedInt {
+                inner: Box::new(Self::read(reader)?),
+            },
+            0b1111 => Self::StringifiedDecimal {
+                inner: Box::new(Self::read(reader)?),
+                precision: Box::new(Self::read(reader)?),
+            },
+            _ => unreachable!(),
+        })
+    }
+}
+
+/// Decodes a sequence of objects compressed with a single engine.
+///
+/// Engines that store data in the header (alphabets, constants, stateful lists) are unpacked once,
+/// when the decoder is created.
+pub enum Decoder {
+    VarInt,
+    FixedInt {
+        bias: i128,
+        length: usize,
+    },
+    Huffman {
+        alphabet: Vec<Value>,
+        codes: HashMap<(usize, u128), usize>,
+        max_length: usize,
+    },
+    String {
+        chars: Box<Decoder>,
+    },
+    StringConcat {
+        words: Box<Decoder>,
+        separator: char,
+    },
+    IntSet {
+        length: Box<Decoder>,
+        min: Box<Decoder>,
+        max: Box<Decoder>,
+        unique: bool,
+    },
+    // Objects that are decoded in advance, e.g. stateful lists and range-coded data
+    Queue {
+        items: VecDeque<Value>,
+    },
+    Vec {
+        length: Box<Decoder>,
+        item: Box<Decoder>,
+    },
+    VecRLE {
+        length: Box<Decoder>,
+        item: Box<Decoder>,
+    },
+    CategorySplit {
+        categories: Vec<Decoder>,
+        category: Box<Decoder>,
+    },
+    Constant {
+        value: Value,
+    },
+    Alphabet {
+        alphabet: Vec<Value>,
+        index: Box<Decoder>,
+    },
+    StringifiedInt {
+        inner: Box<Decoder>,
+    },
+    StringifiedDecimal {
+        inner: Box<Decoder>,
+        precision: Box<Decoder>,
+    },
+    Bytes {
+        inner: Box<Decoder>,
+    },
+    Schema {
+        schema: Schema,
+        newline: Box<Decoder>,
+        fields: Vec<Decoder>,
+    },
+    // Inputs are supplied with set_inputs before the answers are decoded
+    AnswerLines {
+        token: usize,
+        offset: i128,
+        newline: Box<Decoder>,
+        line: Box<Decoder>,
+        inputs: VecDeque<Value>,
+    },
+    Stored,
+    // Records each object and the bits it consumed, so that they can be attributed to engine nodes
+    Profiled {
+        inner: Box<Decoder>,
+        chunks: Vec<Bits>,
+        values: Vec<Value>,
+    },
+}
+
+fn new_boxed(engine: &Engine, profile: bool) -> Result<Box<Decoder>, DecodeError> {
+    Ok(Box::new(Decoder::build(engine, profile)?))
+}
+
+fn decode_embedded(engine: &Engine, data: &Bits) -> Result<Value, DecodeError> {
+    decode_one(engine, &mut BitReader::new(data))
+}
+
+impl Decoder {
+    pub fn new(engine: &Engine) -> Result<Self, DecodeError> {
+        Self::build(engine, false)
+    }
+
+    // Every decoder of per-object data in the tree is wrapped into Profiled
+    pub(crate) fn new_profiled(engine: &Engine) -> Result<Self, DecodeError> {
+        Self::build(engine, true)
+    }
+
+    fn build(engine: &Engine, profile: bool) -> Result<Self, DecodeError> {
+        let decoder = match engine {
+            Engine::VarInt => Self::VarInt,
+            Engine::FixedInt { bias, length } => Self::FixedInt {
+                bias: *bias,
+                length: *length,
+            },
+            Engine::SpecificHuffman {
+                alphabet_engine,
+                alphabet_data,
+                tree,
+            } => {
+                let alphabet = decode_embedded(alphabet_engine, alphabet_data)?.into_vec()?;
+                let bit_length = get_bit_length(alphabet.len() as u128);
+                let mut codes = HashMap::new();
+                let mut max_length = 0;
+
+                fn walk(
+                    reader: &mut BitReader,
+                    bit_length: usize,
+                    code: (usize, u128),
+                    codes: &mut HashMap<(usize, u128), usize>,
+                    max_length: &mut usize,
+                ) -> Result<(), DecodeError> {
+                    if reader.read()? {
+                        codes.insert(code, decompress_fixint(reader, bit_length)? as usize);
+                        *max_length = code.0.max(*max_length);
+                    } else {
+                        if code.0 >= 128 {
+                            return Err(DecodeError::InvalidData("Huffman tree is too deep"));
+                        }
+                        walk(
+                            reader,
+                            bit_length,
+                            (code.0 + 1, code.1 * 2),
+                            codes,
+                            max_length,
+                        )?;
+                        walk(
+                            reader,
+                            bit_length,
+                            (code.0 + 1, code.1 * 2 + 1),
+                            codes,
+                            max_length,
+                        )?;
+                    }
+                    Ok(())
+                }
+                walk(
+                    &mut BitReader::new(tree),
+                    bit_length,
+                    (0, 0),
+                    &mut codes,
+                    &mut max_length,
+                )?;
+
+                if codes.values().any(|i| *i >= alphabet.len()) {
+                    return Err(DecodeError::InvalidData("Huffman symbol is out of range"));
+                }
+
+                Self::Huffman {
+                    alphabet,
+                    codes,
+                    max_length,
+                }
+            }
+            Engine::CanonicalHuffman {
+                alphabet_engine,
+                alphabet_data,
+                lengths_engine,
+                lengths_data,
+            } => {
+                let alphabet = decode_embedded(alphabet_engine, alphabet_data)?.into_vec()?;
+                let lengths = decode_embedded(lengths_engine, lengths_data)?.into_vec()?;
+                if lengths.len() != alphabet.len() {
+                    return Err(DecodeError::InvalidData(
+                        "Huffman alphabet and code lengths mismatch",
+                    ));
+                }
+
+                // Codes are assigned in order of increasing length, as in huffman_unordered
+                let mut codes = HashMap::new();
+                let mut code = 0u128;
+                let mut code_length = 0;
+                for (i, length) in lengths.into_iter().enumerate() {
+                    let length = length.into_usize()?;
+                    if length < code_length || length > 128 {
+                        return Err(DecodeError::InvalidData("invalid Huffman code lengths"));
+                    }
+                    if i > 0 {
+                        code += 1;
+                    }
+                    code = code.checked_shl((length - code_length) as u32).unwrap_or(0);
+                    code_length = length;
+                    if length < 128 && code >> length != 0 {
+                        return Err(DecodeError::InvalidData("invalid Huffman code lengths"));
+                    }
+                    codes.insert((length, code), i);
+                }
+
+                Self::Huffman {
+                    alphabet,
+                    codes,
+                    max_length: code_length,
+                }
+            }
+            Engine::String { chars } => Self::String {
+                chars: new_boxed(chars, profile)?,
+            },
+            Engine::StringConcat { words, separator } => Self::StringConcat {
+                words: new_boxed(words, profile)?,
+                separator: *separator,
+            },
+            Engine::IntSet {
+                length,
+                min,
+                max,
+                unique,
+            } => Self::IntSet {
+                length: new_boxed(length, profile)?,
+                min: new_boxed(min, profile)?,
+                max: new_boxed(max, profile)?,
+                unique: *unique,
+            },
+            Engine::Stateful { inner, data } => Self::Queue {
+                items: decode_embedded(inner, data)?.into_vec()?.into(),
+            },
+            Engine::Vec { length, item } => Self::Vec {
+                length: new_boxed(length, profile)?,
+                item: new_boxed(item, profile)?,
+            },
+            Engine::VecRLE { length, item } => Self::VecRLE {
+                length: new_boxed(length, profile)?,
+                item: new_boxed(item, profile)?,
+            },
+            Engine::CategorySplit {
+                categories,
+                category,
+            } => Self::CategorySplit {
+                categories: categories
+                    .iter()
+                    .map(|category| Self::build(category, profile))
+                    .collect::<Result<_, _>>()?,
+                category: new_boxed(category, profile)?,
+            },
+            Engine::Constant { engine, data } => Self::Constant {
+                value: decode_embedded(engine, data)?,
+            },
+            Engine::Alphabet {
+                alphabet_engine,
+                alphabet_data,
+                index,
+            } => Self::Alphabet {
+                alphabet: decode_embedded(alphabet_engine, alphabet_data)?.into_vec()?,
+                index: new_boxed(index, profile)?,
+            },
+            Engine::StringifiedInt { inner } => Self::StringifiedInt {
+                inner: new_boxed(inner, profile)?,
+            },
+            Engine::StringifiedDecimal { inner, precision } => Self::StringifiedDecimal {
+                inner: new_boxed(inner, profile)?,
+                precision: new_boxed(precision, profile)?,
+            },
+            Engine::Bytes { inner } => Self::Bytes {
+                inner: new_boxed(inner, profile)?,
+            },
+            Engine::RangeCoded {
+                length,
+                alphabet_engine,
+                alphabet_data,
+                freqs_engine,
+                freqs_data,
+                data,
+            } => {
+                let alphabet = decode_embedded(alphabet_engine, alphabet_data)?.into_vec()?;
+                let freqs = decode_freqs(decode_embedded(freqs_engine, freqs_data)?)?;
+                if freqs.len() != alphabet.len() {
+                    return Err(DecodeError::InvalidData(
+                        "alphabet and symbol frequencies mismatch",
+                    ));
+                }
+                let symbols = decode_range_coded(&mut BitReader::new(data), *length, &freqs)?;
+                Self::Queue {
+                    items: symbols
+                        .into_iter()
+                        .map(|symbol| alphabet[symbol].clone())
+                        .collect(),
+                }
+            }
+            Engine::RangeCodedInt {
+                bias,
+                size,
+                length,
+                data,
+            } => Self::Queue {
+                items: decode_range_coded_ints(&mut BitReader::new(data), *length, *size)?
+                    .into_iter()
+                    .map(|num| Value::Int(bias.wrapping_add(num as i128)))
+                    .collect(),
+            },
+            Engine::ContextString {
+                order,
+                length,
+                alphabet_engine,
+                alphabet_data,
+                data,
+            } => {
+                let alphabet = decode_embedded(alphabet_engine, alphabet_data)?
+                    .into_vec()?
+                    .into_iter()
+                    .map(Value::into_char)
+                    .collect::<Result<Vec<char>, _>>()?;
+                let strings = decode_context_strings(
+                    &mut BitReader::new(data),
+                    *length,
+                    alphabet.len(),
+                    *order,
+                )?;
+                Self::Queue {
+                    items: strings
+                        .into_iter()
+                        .map(|string| {
+                            Value::Str(string.into_iter().map(|symbol| alphabet[symbol]).collect())
+                        })
+                        .collect(),
+                }
+            }
+            Engine::Schema {
+                schema,
+                newline,
+                fields,
+            } => Self::Schema {
+                schema: schema.clone(),
+                newline: new_boxed(newline, profile)?,
+                fields: fields
+                    .iter()
+                    .map(|field| Self::build(field, profile))
+                    .collect::<Result<_, _>>()?,
+            },
+            Engine::AnswerLines {
+                token,
+                offset,
+                newline,
+                line,
+            } => Self::AnswerLines {
+                token: *token,
+                offset: *offset,
+                newline: new_boxed(newline, profile)?,
+                line: new_boxed(line, profile)?,
+                inputs: VecDeque::new(),
+            },
+            Engine::Stored => Self::Stored,
+        };
+        Ok(if profile {
+            Self::Profiled {
+                inner: Box::new(decoder),
+                chunks: Vec::new(),
+                values: Vec::new(),
+            }
+        } else {
+            decoder
+        })
+    }
+
+    // Answers compressed with AnswerLines are restored from their inputs, in the same order. Other
+    // decoders don't depend on inputs
+    pub(crate) fn set_inputs(&mut self, inputs: Vec<Value>) {
+        match self {
+            Self::AnswerLines { inputs: queue, .. } => *queue = inputs.into(),
+            Self::Profiled { inner, .. } => inner.set_inputs(inputs),
+            _ => {}
+        }
+    }
+
+    pub fn decode(&mut self, reader: &mut BitReader) -> Result<Value, DecodeError> {
+        Ok(match self {
+            Self::VarInt => Value::Int(decompress_varint(reader)?),
+            Self::FixedInt { bias, length } => {
+                Value::Int(bias.wrapping_add(decompress_fixint(reader, *length)? as i128))
+            }
+            Self::Huffman {
+                alphabet,
+                codes,
+                max_length,
+            } => {
+                let mut code = (0, 0);
+                loop {
+                    if let Some(i) = codes.get(&code) {
+                        break alphabet[*i].clone();
+                    }
+                    if code.0 >= *max_length {
+                        return Err(DecodeError::InvalidData("unknown Huffman code"));
+                    }
+                    code = (code.0 + 1, code.1 * 2 + reader.read()? as u128);
+                }
+            }
+            Self::String { chars } => Value::Str(
+                chars
+                    .decode(reader)?
+                    .into_vec()?
+                    .into_iter()
+                    .map(Value::into_char)
+                    .collect::<Result<_, _>>()?,
+            ),
+            Self::StringConcat { words, separator } => Value::Str(
+                words
+                    .decode(reader)?
+                    .into_vec()?
+                    .into_iter()
+                    .map(Value::into_string)
+                    .collect::<Result<Vec<_>, _>>()?
+                    .join(&separator.to_string()),
+            ),
+            Self::IntSet {
+                length,
+                min,
+                max,
+                unique,
+            } => {
+                let length = length.decode(reader)?.into_usize()?;
+                reader.reserve_items(length)?;
+                let min = min.decode(reader)?.into_int()?;
+                let max = max.decode(reader)?.into_int()?;
+                let mut nums = Vec::new();
+                if *unique {
+                    if length < 2 {
+                        return Err(DecodeError::InvalidData("unique set is too short"));
+                    }
+                    nums.push(min);
+                    decode_ordered_set_slice(
+                        reader,
+                        length - 2,
+                        min + 1,
+                        max - 1,
+                        true,
+                        &mut nums,
+                    )?;
+                    nums.push(max);
+                } else {
+                    decode_ordered_set_slice(reader, length, min, max, false, &mut nums)?;
+                }
+                Value::Vec(nums.into_iter().map(Value::Int).collect())
+            }
+            Self::Queue { items } => items
+                .pop_front()
+                .ok_or(DecodeError::InvalidData("too many objects requested"))?,
+            Self::Vec { length, item } => {
+                let length = length.decode(reader)?.into_usize()?;
+                reader.reserve_items(length)?;
+                let mut items = Vec::new();
+                for _ in 0..length {
+                    items.push(item.decode(reader)?);
+                }
+                Value::Vec(items)
+            }
+            Self::VecRLE { length, item } => {
+                let run_lengths = length.decode(reader)?.into_vec()?;
+                let run_values = item.decode(reader)?.into_vec()?;
+                if run_lengths.len() != run_values.len() {
+                    return Err(DecodeError::InvalidData("run lengths and values mismatch"));
+                }
+                let mut items = Vec::new();
+                for (run_length, value) in run_lengths.into_iter().zip(run_values) {
+                    let run_length = run_length.into_usize()?;
+                    reader.reserve_items(run_length)?;
+                    for _ in 0..run_length {
+                        items.push(value.clone());
+                    }
+                }
+                Value::Vec(items)
+            }
+            Self::CategorySplit {
+                categories,
+                category,
+            } => {
+                let i = category.decode(reader)?.into_usize()?;
+                categories
+                    .get_mut(i)
+                    .ok_or(DecodeError::InvalidData("category is out of range"))?
+                    .decode(reader)?
+            }
+            Self::Constant { value } => value.clone(),
+            Self::Alphabet { alphabet, index } => {
+                let i = index.decode(reader)?.into_usize()?;
+                alphabet
+                    .get(i)
+                    .ok_or(DecodeError::InvalidData("alphabet index is out of range"))?
+                    .clone()
+            }
+            Self::StringifiedInt { inner } => {
+                Value::Str(inner.decode(reader)?.into_int()?.to_string())
+            }
+            Self::StringifiedDecimal { inner, precision } => {
+                let mut s = inner.decode(reader)?.into_int()?.to_string();
+                let precision = precision.decode(reader)?.into_usize()?;
+                if precision > s.len() {
+                    return Err(DecodeError::InvalidData("precision is out of range"));
+                }
+                s.insert(s.len() - precision, '.');
+                Value::Str(s)
+            }
+            Self::Bytes { inner } => Value::Bytes(
+                inner
+                    .decode(reader)?
+                    .into_vec()?
+                    .into_iter()
+                    .map(|byte| {
+                        u8::try_from(byte.into_int()?)
+                            .map_err(|_| DecodeError::InvalidData("expected a byte"))
+                    })
+                    .collect::<Result<_, _>>()?,
+            ),
+            Self::Schema {
+                schema,
+                newline,
+                fields,
+            } => {
+                let newline = newline.decode(reader)?.into_usize()? != 0;
+                let mut text = schema.decode(fields, reader)?;
+                if newline {
+                    text.push('\n');
+                }
+                Value::Str(text)
+            }
+            Self::AnswerLines {
+                token,
+                offset,
+                newline,
+                line,
+                inputs,
+            } => {
+                // The count is read from the text of the input, whichever kind it was decoded as
+                let input = match inputs
+                    .pop_front()
+                    .ok_or(DecodeError::InvalidData("answers need their inputs"))?
+                {
+                    Value::Bytes(bytes) => String::from_utf8(bytes)
+                        .map_err(|_| DecodeError::InvalidData("input is not UTF-8"))?,
+                    input => input.into_string()?,
+                };
+                let n_lines = line_count(&input, *token, *offset)
+                    .ok_or(DecodeError::InvalidData("input has no line count"))?;
+                reader.reserve_items(n_lines)?;
+                let newline = newline.decode(reader)?.into_usize()? != 0;
+                let mut lines = Vec::new();
+                for _ in 0..n_lines {
+                    lines.push(line.decode(reader)?.into_string()?);
+                }
+                let mut text = lines.join("\n");
+                if newline {
+                    text.push('\n');
+                }
+                Value::Str(text)
+            }
+            Self::Stored => {
+                let text = reader.read()?;
+                let length = read_count(reader)?;
+                let mut bytes = Vec::new();
+                for _ in 0..length {
+                    bytes.push(decompress_fixint(reader, 8)? as u8);
+                }
+                if text {
+                    Value::Str(
+                        String::from_utf8(bytes)
+                            .map_err(|_| DecodeError::InvalidData("stored text is not UTF-8"))?,
+                    )
+                } else {
+                    Value::Bytes(bytes)
+                }
+            }
+            Self::Profiled {
+                inner,
+                chunks,
+                values,
+            } => {
+                let start = reader.position();
+                let value = inner.decode(reader)?;
+                chunks.push(reader.consumed_since(start));
+                values.push(value.clone());
+                value
+            }
+        })
+    }
+}
+
+fn decode_ordered_set_slice(
+    reader: &mut BitReader,
+    length: usize,
+    min: i128,
+    max: i128,
+    unique: bool,
+    nums: &mut Vec<i128>,
+) -> Result<(), DecodeError> {
+    if length == 0 {
+        return Ok(());
+    }
+
+    let m = length / 2;
+
+    if unique {
+        let m_min = min + (m as i128);
+        let m_max = max - (length - m - 1) as i128;
+        if m_min > m_max {
+            return Err(DecodeError::InvalidData("invalid integer set bounds"));
+        }
+        let bit_length = get_bit_length((m_max - m_min) as u128);
+        let mid = m_min + decompress_fixint(reader, bit_length)? as i128;
+        decode_ordered_set_slice(reader, m, min, mid - 1, true, nums)?;
+        nums.push(mid);
+        decode_ordered_set_slice(reader, length - m - 1, mid + 1, max, true, nums)?;
+    } else {
+        if min > max {
+            return Err(DecodeError::InvalidData("invalid integer set bounds"));
+        }
+        let bit_length = get_bit_length((max - min) as u128);
+        let mid = min + decompress_fixint(reader, bit_length)? as i128;
+        decode_ordered_set_slice(reader, m, min, mid, false, nums)?;
+        nums.push(mid);
+        decode_ordered_set_slice(reader, length - m - 1, mid, max, false, nums)?;
+    }
+
+    Ok(())
+}
+
+/// Reads an engine and `n` objects compressed with it, as written by
+/// [`MultiCompressedData::to_bits`](crate::MultiCompressedData::to_bits).
+pub fn decode_group(reader: &mut BitReader, n: usize) -> Result<Vec<Value>, DecodeError> {
+    let engine = Engine::read(reader)?;
+    let mut decoder = Decoder::new(&engine)?;
+    reader.reserve_items(n)?;
+    let mut objs = Vec::new();
+    for _ in 0..n {
+        objs.push(decoder.decode(reader)?);
+    }
+    Ok(objs)
+}
@@ -0,0 +1,360 @@
+use crate::autocompress::{autocompress, AutoCompressOpts, SearchContext};
+use crate::bits::{BitReader, Bits};
+use crate::compress::{Compress, Engine, MultiCompressedData};
+use crate::compress_file::FileContents;
+use crate::decompress::{DecodeError, Decoder, Value};
+use crate::schema::Schema;
+use std::collections::HashMap;
+use std::fmt::Write;
+
+// Alternatives rejected in favor of a result, collected in the search context of explain mode.
+// Results are identified by their engine and data, so that they can be found again when the final
+// engine tree is walked
+type ChoiceKey = (usize, usize, Vec<u8>);
+pub(crate) type Choices = HashMap<ChoiceKey, Vec<(&'static str, usize)>>;
+
+fn choice_key(engine: &Engine, chunks: &[Bits]) -> ChoiceKey {
+    let mut bits = engine.to_bits();
+    for chunk in chunks {
+        bits.extend(chunk);
+    }
+    (chunks.len(), bits.len(), bits.to_bytes())
+}
+
+pub(crate) fn record_choice(
+    choices: &mut Choices,
+    chosen: &MultiCompressedData,
+    rejected: Vec<(&'static str, usize)>,
+) {
+    // The same subproblem may be met several times during the search
+    let recorded = choices
+        .entry(choice_key(&chosen.engine, &chosen.binary_data))
+        .or_default();
+    for alternative in rejected {
+        if !recorded.contains(&alternative) {
+            recorded.push(alternative);
+        }
+    }
+}
+
+/// Compresses a group of objects and describes the resulting engine tree.
+///
+/// Each node is annotated with the bits its header takes, including embedded data, the bits it
+/// contributes to the objects' data, and the alternatives that were rejected in its favor, along
+/// with their total weights.
+pub fn explain<T: Compress>(objs: &[T], opts: AutoCompressOpts) -> Result<String, DecodeError> {
+    let objs_refs: Vec<&T> = objs.iter().collect();
+    report(|opts| autocompress(&objs_refs, opts), opts, Vec::new())
+}
+
+/// Compresses files that follow a schema as [`compress_with_schema`](crate::compress_with_schema)
+/// does and describes the resulting engine tree, as [`explain`] does.
+pub fn explain_with_schema(
+    files: &[FileContents],
+    schema: &Schema,
+    opts: AutoCompressOpts,
+) -> Result<String, DecodeError> {
+    report(
+        |opts| crate::compress_with_schema(files, schema, opts),
+        opts,
+        Vec::new(),
+    )
+}
+
+/// Compresses answers to test inputs as [`compress_answers`](crate::compress_answers) does and
+/// describes the resulting engine tree, as [`explain`] does.
+pub fn explain_answers(
+    inputs: &[FileContents],
+    answers: &[FileContents],
+    opts: AutoCompressOpts,
+) -> Result<String, DecodeError> {
+    report(
+        |opts| crate::compress_answers(inputs, answers, opts),
+        opts,
+        inputs.iter().map(FileContents::to_value).collect(),
+    )
+}
+
+// Runs the compression while recording choices, then walks the engine tree. Inputs are only used
+// by engines of answers
+fn report(
+    compress: impl FnOnce(AutoCompressOpts) -> MultiCompressedData,
+    opts: AutoCompressOpts,
+    inputs: Vec<Value>,
+) -> Result<String, DecodeError> {
+    let context = SearchContext::recording_choices();
+    let data = compress(AutoCompressOpts {
+        context: Some(&context),
+        ..opts
+    });
+    let choices = context.into_choices();
+
+    let mut out = String::new();
+    writeln!(
+        out,
+        "{}, {} bits in total",
+        plural(data.binary_data.len(), "object"),
+        data.weight()
+    )
+    .unwrap();
+    let mut decoder = Decoder::new_profiled(&data.engine)?;
+    decoder.set_inputs(inputs);
+    for chunk in &data.binary_data {
+        decoder.decode(&mut BitReader::new(chunk))?;
+    }
+    explain_node(&mut out, &choices, 0, "root", &data.engine, &decoder)?;
+    Ok(out)
+}
+
+fn explain_node(
+    out: &mut String,
+    choices: &Choices,
+    depth: usize,
+    name: &str,
+    engine: &Engine,
+    decoder: &Decoder,
+) -> Result<(), DecodeError> {
+    let Decoder::Profiled { inner, chunks, .. } = decoder else {
+        unreachable!()
+    };
+    let indent = "  ".repeat(depth);
+    writeln!(
+        out,
+        "{indent}{name}: {}, header {} bits, payload {} bits in {}",
+        describe(engine),
+        engine.weight(),
+        chunks.iter().map(|chunk| chunk.len()).sum::<usize>(),
+        plural(chunks.len(), "object"),
+    )
+    .unwrap();
+    if let Some(rejected) = choices.get(&choice_key(engine, chunks)) {
+        for (alternative, weight) in rejected {
+            writeln!(out, "{indent}  rejected {alternative}: {weight} bits").unwrap();
+        }
+    }
+
+    for (child_name, child_engine, data) in embedded_children(engine) {
+        // Embedded objects are decoded once, as when the decoder is created
+        let mut child_decoder = Decoder::new_profiled(child_engine)?;
+        child_decoder.decode(&mut BitReader::new(data))?;
+        let child_name = format!("{child_name} (in header)");
+        explain_node(
+            out,
+            choices,
+            depth + 1,
+            &child_name,
+            child_engine,
+            &child_decoder,
+        )?;
+    }
+
+    for (child_name, child_engine, child_decoder) in payload_children(engine, inner) {
+        explain_node(
+            out,
+            choices,
+            depth + 1,
+            &child_name,
+            child_engine,
+            child_decoder,
+        )?;
+    }
+    Ok(())
+}
+
+fn plural(n: usize, noun: &str) -> String {
+    if n == 1 {
+        format!("1 {noun}")
+    } else {
+        format!("{n} {noun}s")
+    }
+}
+
+fn describe(engine: &Engine) -> String {
+    match engine {
+        Engine::VarInt => "VarInt".to_string(),
+        Engine::FixedInt { bias, length } => format!("FixedInt(bias {bias}, {length} bits)"),
+        Engine::SpecificHuffman { .. } => "SpecificHuffman".to_string(),
+        Engine::CanonicalHuffman { .. } => "CanonicalHuffman".to_string(),
+        Engine::String { .. } => "String".to_string(),
+        Engine::StringConcat { separator, .. } => format!("StringConcat({separator:?})"),
+        Engine::IntSet { unique, .. } => {
+            format!("IntSet({})", if *unique { "unique" } else { "repeated" })
+        }
+        Engine::Stateful { .. } => "Stateful".to_string(),
+        Engine::Vec { .. } => "Vec".to_string(),
+        Engine::VecRLE { .. } => "VecRLE".to_string(),
+        Engine::CategorySplit { categories, .. } => {
+            format!("CategorySplit({} categories)", categories.len())
+        }
+        Engine::Constant { .. } => "Constant".to_string(),
+        Engine::Alphabet { .. } => "Alphabet".to_string(),
+        Engine::StringifiedInt { .. } => "StringifiedInt".to_string(),
+        Engine::StringifiedDecimal { .. } => "StringifiedDecimal".to_string(),
+        Engine::Bytes { .. } => "Bytes".to_string(),
+        Engine::RangeCoded { length, .. } => format!("RangeCoded({length} symbols)"),
+        Engine::RangeCodedInt {
+            bias, size, length, ..
+        } => {
+            format!("RangeCodedInt(bias {bias}, size {size}, {length} numbers)")
+        }
+        Engine::ContextString { order, length, .. } => {
+            format!("ContextString(order {order}, {length} strings)")
+        }
+        Engine::Schema { schema, .. } => format!("Schema({schema})"),
+        Engine::AnswerLines { token, offset, .. } => {
+            format!("AnswerLines(token {token} of input {offset:+})")
+        }
+        Engine::Stored => "Stored".to_string(),
+    }
+}
+
+// Engines of objects that are stored in the header, along with their data
+fn embedded_children(engine: &Engine) -> Vec<(&'static str, &Engine, &Bits)> {
+    match engine {
+        Engine::SpecificHuffman {
+            alphabet_engine,
+            alphabet_data,
+            ..
+        } => vec![("alphabet", alphabet_engine, alphabet_data)],
+        Engine::CanonicalHuffman {
+            alphabet_engine,
+            alphabet_data,
+            lengths_engine,
+            lengths_data,
+        } => vec![
+            ("alphabet", alphabet_engine, alphabet_data),
+            ("lengths", lengths_engine, lengths_data),
+        ],
+        Engine::Stateful { inner, data } => vec![("list", inner, data)],
+        Engine::Constant { engine, data } => vec![("value", engine, data)],
+        Engine::Alphabet {
+            alphabet_engine,
+            alphabet_data,
+            ..
+        } => vec![("alphabet", alphabet_engine, alphabet_data)],
+        Engine::RangeCoded {
+            alphabet_engine,
+            alphabet_data,
+            freqs_engine,
+            freqs_data,
+            ..
+        } => vec![
+            ("alphabet", alphabet_engine, alphabet_data),
+            ("frequencies", freqs_engine, freqs_data),
+        ],
+        Engine::ContextString {
+            alphabet_engine,
+            alphabet_data,
+            ..
+        } => vec![("alphabet", alphabet_engine, alphabet_data)],
+        _ => Vec::new(),
+    }
+}
+
+// Engines of the parts of per-object data, along with their profiled decoders
+pub(crate) fn payload_children<'a>(
+    engine: &'a Engine,
+    decoder: &'a Decoder,
+) -> Vec<(String, &'a Engine, &'a Decoder)> {
+    match (engine, decoder) {
+        (Engine::String { chars }, Decoder::String { chars: d }) => {
+            vec![("chars".to_string(), chars, d)]
+        }
+        (Engine::StringConcat { words, .. }, Decoder::StringConcat { words: d, .. }) => {
+            vec![("words".to_string(), words, d)]
+        }
+        (
+            Engine::IntSet {
+                length, min, max, ..
+            },
+            Decoder::IntSet {
+                length: length_d,
+                min: min_d,
+                max: max_d,
+                ..
+            },
+        ) => vec![
+            ("length".to_string(), length, length_d),
+            ("min".to_string(), min, min_d),
+            ("max".to_string(), max, max_d),
+        ],
+        (
+            Engine::Vec { length, item },
+            Decoder::Vec {
+                length: length_d,
+                item: item_d,
+            },
+        )
+        | (
+            Engine::VecRLE { length, item },
+            Decoder::VecRLE {
+                length: length_d,
+                item: item_d,
+            },
+        ) => vec![
+            ("length".to_string(), length, length_d),
+            ("item".to_string(), item, item_d),
+        ],
+        (
+            Engine::CategorySplit {
+                categories,
+                category,
+            },
+            Decoder::CategorySplit {
+                categories: categories_d,
+                category: category_d,
+            },
+        ) => {
+            let mut children = vec![("category".to_string(), &**category, &**category_d)];
+            for (i, (engine, decoder)) in categories.iter().zip(categories_d).enumerate() {
+                children.push((format!("category {i}"), engine, decoder));
+            }
+            children
+        }
+        (Engine::Alphabet { index, .. }, Decoder::Alphabet { index: d, .. }) => {
+            vec![("index".to_string(), index, d)]
+        }
+        (Engine::StringifiedInt { inner }, Decoder::StringifiedInt { inner: d })
+        | (Engine::Bytes { inner }, Decoder::Bytes { inner: d }) => {
+            vec![("inner".to_string(), inner, d)]
+        }
+        (
+            Engine::StringifiedDecimal { inner, precision },
+            Decoder::StringifiedDecimal {
+                inner: inner_d,
+                precision: precision_d,
+            },
+        ) => vec![
+            ("inner".to_string(), inner, inner_d),
+            ("precision".to_string(), precision, precision_d),
+        ],
+        (
+            Engine::Schema {
+                newline, fields, ..
+            },
+            Decoder::Schema {
+                newline: newline_d,
+                fields: fields_d,
+                ..
+            },
+        ) => {
+            let mut children = vec![("newline".to_string(), &**newline, &**newline_d)];
+            for (i, (engine, decoder)) in fields.iter().zip(fields_d).enumerate() {
+                children.push((format!("field {i}"), engine, decoder));
+            }
+            children
+        }
+        (
+            Engine::AnswerLines { newline, line, .. },
+            Decoder::AnswerLines {
+                newline: newline_d,
+                line: line_d,
+                ..
+            },
+        ) => vec![
+            ("newline".to_string(), newline, newline_d),
+            ("line".to_string(), line, line_d),
+        ],
+        _ => Vec::new(),
+    }
+}
@@ -0,0 +1,331 @@
+use crate::bits::Bits;
+use crate::compress::Engine;
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+enum Field<'a> {
+    Int(i128),
+    UInt(u128),
+    Bool(bool),
+    Char(char),
+    Str(String),
+    // Embedded data is exported as its length in bits
+    Data(&'a Bits),
+    Engine(&'a Engine),
+    Engines(&'a [Engine]),
+}
+
+impl Engine {
+    fn fields(&self) -> (&'static str, Vec<(&'static str, Field<'_>)>) {
+        match self {
+            Self::VarInt => ("VarInt", vec![]),
+            Self::FixedInt { bias, length } => (
+                "FixedInt",
+                vec![
+                    ("bias", Field::Int(*bias)),
+                    ("length", Field::UInt(*length as u128)),
+                ],
+            ),
+            Self::SpecificHuffman {
+                alphabet_engine,
+                alphabet_data,
+                tree,
+            } => (
+                "SpecificHuffman",
+                vec![
+                    ("alphabet_engine", Field::Engine(alphabet_engine)),
+                    ("alphabet_data", Field::Data(alphabet_data)),
+                    ("tree", Field::Data(tree)),
+                ],
+            ),
+            Self::CanonicalHuffman {
+                alphabet_engine,
+                alphabet_data,
+                lengths_engine,
+                lengths_data,
+            } => (
+                "CanonicalHuffman",
+                vec![
+                    ("alphabet_engine", Field::Engine(alphabet_engine)),
+                    ("alphabet_data", Field::Data(alphabet_data)),
+                    ("lengths_engine", Field::Engine(lengths_engine)),
+                    ("lengths_data", Field::Data(lengths_data)),
+                ],
+            ),
+            Self::String { chars } => ("String", vec![("chars", Field::Engine(chars))]),
+            Self::StringConcat { words, separator } => (
+                "StringConcat",
+                vec![
+                    ("words", Field::Engine(words)),
+                    ("separator", Field::Char(*separator)),
+                ],
+            ),
+            Self::IntSet {
+                length,
+                min,
+                max,
+                unique,
+            } => (
+                "IntSet",
+                vec![
+                    ("length", Field::Engine(length)),
+                    ("min", Field::Engine(min)),
+                    ("max", Field::Engine(max)),
+                    ("unique", Field::Bool(*unique)),
+                ],
+            ),
+            Self::Stateful { inner, data } => (
+                "Stateful",
+                vec![("inner", Field::Engine(inner)), ("data", Field::Data(data))],
+            ),
+            Self::Vec { length, item } => (
+                "Vec",
+                vec![
+                    ("length", Field::Engine(length)),
+                    ("item", Field::Engine(item)),
+                ],
+            ),
+            Self::VecRLE { length, item } => (
+                "VecRLE",
+                vec![
+                    ("length", Field::Engine(length)),
+                    ("item", Field::Engine(item)),
+                ],
+            ),
+            Self::CategorySplit {
+                categories,
+                category,
+            } => (
+                "CategorySplit",
+                vec![
+                    ("categories", Field::Engines(categories)),
+                    ("category", Field::Engine(category)),
+                ],
+            ),
+            Self::Constant { engine, data } => (
+                "Constant",
+                vec![
+                    ("engine", Field::Engine(engine)),
+                    ("data", Field::Data(data)),
+                ],
+            ),
+            Self::Alphabet {
+                alphabet_engine,
+                alphabet_data,
+                index,
+            } => (
+                "Alphabet",
+                vec![
+                    ("alphabet_engine", Field::Engine(alphabet_engine)),
+                    ("alphabet_data", Field::Data(alphabet_data)),
+                    ("index", Field::Engine(index)),
+                ],
+            ),
+            Self::StringifiedInt { inner } => {
+                ("StringifiedInt", vec![("inner", Field::Engine(inner))])
+            }
+            Self::StringifiedDecimal { inner, precision } => (
+                "StringifiedDecimal",
+                vec![
+                    ("inner", Field::Engine(inner)),
+                    ("precision", Field::Engine(precision)),
+                ],
+            ),
+            Self::Bytes { inner } => ("Bytes", vec![("inner", Field::Engine(inner))]),
+            Self::RangeCoded {
+                length,
+                alphabet_engine,
+                alphabet_data,
+                freqs_engine,
+                freqs_data,
+                data,
+            } => (
+                "RangeCoded",
+                vec![
+                    ("length", Field::UInt(*length as u128)),
+                    ("alphabet_engine", Field::Engine(alphabet_engine)),
+                    ("alphabet_data", Field::Data(alphabet_data)),
+                    ("freqs_engine", Field::Engine(freqs_engine)),
+                    ("freqs_data", Field::Data(freqs_data)),
+                    ("data", Field::Data(data)),
+                ],
+            ),
+            Self::RangeCodedInt {
+                bias,
+                size,
+                length,
+                data,
+            } => (
+                "RangeCodedInt",
+                vec![
+                    ("bias", Field::Int(*bias)),
+                    ("size", Field::UInt(*size)),
+                    ("length", Field::UInt(*length as u128)),
+                    ("data", Field::Data(data)),
+                ],
+            ),
+            Self::ContextString {
+                order,
+                length,
+                alphabet_engine,
+                alphabet_data,
+                data,
+            } => (
+                "ContextString",
+                vec![
+                    ("order", Field::UInt(*order as u128)),
+                    ("length", Field::UInt(*length as u128)),
+                    ("alphabet_engine", Field::Engine(alphabet_engine)),
+                    ("alphabet_data", Field::Data(alphabet_data)),
+                    ("data", Field::Data(data)),
+                ],
+            ),
+            Self::Schema {
+                schema,
+                newline,
+                fields,
+            } => (
+                "Schema",
+                vec![
+                    ("schema", Field::Str(schema.to_string())),
+                    ("newline", Field::Engine(newline)),
+                    ("fields", Field::Engines(fields)),
+                ],
+            ),
+            Self::AnswerLines {
+                token,
+                offset,
+                newline,
+                line,
+            } => (
+                "AnswerLines",
+                vec![
+                    ("token", Field::UInt(*token as u128)),
+                    ("offset", Field::Int(*offset)),
+                    ("newline", Field::Engine(newline)),
+                    ("line", Field::Engine(line)),
+                ],
+            ),
+            Self::Stored => ("Stored", vec![]),
+        }
+    }
+
+    /// Serializes the engine tree to JSON. Each node is an object with a `type` key and a key per
+    /// field. Embedded data is represented by its length in bits.
+    pub fn to_json(&self) -> String {
+        let mut out = String::new();
+        self.write_json(&mut out);
+        out
+    }
+
+    fn write_json(&self, out: &mut String) {
+        let (name, fields) = self.fields();
+        write!(out, "{{\"type\":{}", json_string(name)).unwrap();
+        for (key, field) in fields {
+            write!(out, ",{}:", json_string(key)).unwrap();
+            match field {
+                Field::Int(value) => write!(out, "{value}").unwrap(),
+                Field::UInt(value) => write!(out, "{value}").unwrap(),
+                Field::Bool(value) => write!(out, "{value}").unwrap(),
+                Field::Char(value) => out.push_str(&json_string(&value.to_string())),
+                Field::Str(value) => out.push_str(&json_string(&value)),
+                Field::Data(data) => write!(out, "{}", data.len()).unwrap(),
+                Field::Engine(engine) => engine.write_json(out),
+                Field::Engines(engines) => {
+                    out.push('[');
+                    for (i, engine) in engines.iter().enumerate() {
+                        if i > 0 {
+                            out.push(',');
+                        }
+                        engine.write_json(out);
+                    }
+                    out.push(']');
+                }
+            }
+        }
+        out.push('}');
+    }
+
+    /// Serializes the engine tree to a Graphviz DOT digraph. Nodes list their parameters, and
+    /// edges are labeled with field names.
+    pub fn to_dot(&self) -> String {
+        let mut out = String::from("digraph engine {\n    node [shape=box];\n");
+        self.write_dot(&mut out, &mut 0);
+        out.push_str("}\n");
+        out
+    }
+
+    // Returns the id of the node
+    fn write_dot(&self, out: &mut String, next_id: &mut usize) -> usize {
+        let id = *next_id;
+        *next_id += 1;
+
+        let (name, fields) = self.fields();
+        let mut label = name.to_string();
+        let mut edges = Vec::new();
+        for (key, field) in fields {
+            match field {
+                Field::Int(value) => write!(label, "\n{key} = {value}").unwrap(),
+                Field::UInt(value) => write!(label, "\n{key} = {value}").unwrap(),
+                Field::Bool(value) => write!(label, "\n{key} = {value}").unwrap(),
+                Field::Char(value) => write!(label, "\n{key} = {value:?}").unwrap(),
+                Field::Str(value) => write!(label, "\n{key} = {value:?}").unwrap(),
+                Field::Data(data) => write!(label, "\n{key} = {} bits", data.len()).unwrap(),
+                Field::Engine(engine) => edges.push((key.to_string(), engine)),
+                Field::Engines(engines) => {
+                    for (i, engine) in engines.iter().enumerate() {
+                        edges.push((format!("{key}[{i}]"), engine));
+                    }
+                }
+            }
+        }
+        writeln!(out, "    n{id} [label={}];", json_string(&label)).unwrap();
+
+        for (key, engine) in edges {
+            let child_id = engine.write_dot(out, next_id);
+            writeln!(
+                out,
+                "    n{id} -> n{child_id} [label={}];",
+                json_string(&key)
+            )
+            .unwrap();
+        }
+        id
+    }
+
+    // Names of the variants used in the tree, including the engines of embedded data
+    pub(crate) fn collect_variants(&self, variants: &mut BTreeSet<&'static str>) {
+        let (name, fields) = self.fields();
+        variants.insert(name);
+        for (_, field) in fields {
+            match field {
+                Field::Engine(engine) => engine.collect_variants(variants),
+                Field::Engines(engines) => {
+                    for engine in engines {
+                        engine.collect_variants(variants);
+                    }
+                }
+                _ => {}
+            }
+        }
+    }
+}
+
+// Also used for DOT, where labels contain no control characters other than line breaks, which are
+// escaped the same way
+fn json_string(s: &str) -> String {
+    let mut out = String::from("\"");
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => write!(out, "\\u{:04x}", c as u32).unwrap(),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+    out
+}
@@ -0,0 +1,478 @@
+use crate::autocompress::{autocompress, AutoCompressOpts};
+use crate::bits::BitReader;
+use crate::compress::{Compress, Engine};
+use crate::decompress::{Decoder, Value};
+use std::collections::{BTreeSet, HashSet};
+
+// The engine tree describes the structure of objects, e.g. lines split into tokens, while the
+// ranges of values come from the objects themselves, as the engine only bounds them loosely
+struct Desc {
+    text: String,
+    children: Vec<Desc>,
+}
+
+impl Desc {
+    fn leaf(text: String) -> Self {
+        Self {
+            text,
+            children: Vec::new(),
+        }
+    }
+
+    fn render(&self, out: &mut String, depth: usize) {
+        out.push_str(&"  ".repeat(depth));
+        out.push_str(&self.text);
+        out.push('\n');
+        for child in &self.children {
+            child.render(out, depth + 1);
+        }
+    }
+}
+
+/// Compresses a group of objects and describes their format as inferred from the engine tree, e.g.
+/// `line 1: 2 tokens, each integer in [1, 200000]`.
+pub fn describe_format<T: Compress>(objs: &[T], opts: AutoCompressOpts) -> String {
+    // A final line break would be described as an empty last line, so text is described without
+    // it. It's only mentioned if some objects lack it
+    let texts: Option<Vec<String>> = objs
+        .iter()
+        .map(|obj| match obj.to_value() {
+            Value::Str(s) => Some(s),
+            _ => None,
+        })
+        .collect();
+    let Some(texts) = texts.filter(|texts| texts.iter().any(|s| s.ends_with('\n'))) else {
+        return describe_objects(objs, opts);
+    };
+    let n_ending = texts.iter().filter(|s| s.ends_with('\n')).count();
+    let stripped: Vec<String> = texts
+        .iter()
+        .map(|s| s.strip_suffix('\n').unwrap_or(s).to_string())
+        .collect();
+    let mut out = describe_objects(&stripped, opts);
+    if n_ending < texts.len() {
+        out.push_str(&format!(
+            "Line break at the end: {n_ending} of {} objects\n",
+            texts.len()
+        ));
+    }
+    out
+}
+
+fn describe_objects<T: Compress>(objs: &[T], opts: AutoCompressOpts) -> String {
+    let objs_refs: Vec<&T> = objs.iter().collect();
+    let data = autocompress(&objs_refs, opts);
+    let mut decoder = Decoder::new_profiled(&data.engine).expect("Failed to decode own output");
+    for chunk in &data.binary_data {
+        decoder
+            .decode(&mut BitReader::new(chunk))
+            .expect("Failed to decode own output");
+    }
+
+    let desc = describe(&data.engine, &decoder);
+    let mut out = String::new();
+    Desc {
+        text: format!("Each object: {}", desc.text),
+        children: desc.children,
+    }
+    .render(&mut out, 0);
+    out
+}
+
+fn profiled(decoder: &Decoder) -> (&Decoder, &[Value]) {
+    match decoder {
+        Decoder::Profiled { inner, values, .. } => (inner, values),
+        _ => unreachable!(),
+    }
+}
+
+fn describe(engine: &Engine, decoder: &Decoder) -> Desc {
+    let (inner, values) = profiled(decoder);
+    match (engine, inner) {
+        (
+            Engine::StringConcat { words, separator },
+            Decoder::StringConcat { words: words_d, .. },
+        ) => {
+            let noun = match separator {
+                '\n' => "line",
+                ' ' => "token",
+                _ => "part",
+            };
+            let desc = describe_parts(words, words_d, noun);
+            if noun == "part" {
+                Desc {
+                    text: format!("{}, separated by {separator:?}", desc.text),
+                    children: desc.children,
+                }
+            } else {
+                desc
+            }
+        }
+        (
+            Engine::Vec { item, .. },
+            Decoder::Vec {
+                length: length_d,
+                item: item_d,
+            },
+        ) => describe_vec(length_d, item, item_d, "item"),
+        (
+            Engine::CategorySplit { categories, .. },
+            Decoder::CategorySplit {
+                categories: categories_d,
+                ..
+            },
+        ) => describe_kinds(categories, categories_d, values.len(), describe),
+        _ => Desc::leaf(summarize(values)),
+    }
+}
+
+// Describes the list of parts a string is split into
+fn describe_parts(engine: &Engine, decoder: &Decoder, noun: &str) -> Desc {
+    let (inner, values) = profiled(decoder);
+    match (engine, inner) {
+        (
+            Engine::Vec { item, .. },
+            Decoder::Vec {
+                length: length_d,
+                item: item_d,
+            },
+        ) => describe_vec(length_d, item, item_d, noun),
+        // E.g. lines of different kinds with different numbers of tokens
+        (
+            Engine::CategorySplit { categories, .. },
+            Decoder::CategorySplit {
+                categories: categories_d,
+                ..
+            },
+        ) => describe_kinds(categories, categories_d, values.len(), |engine, decoder| {
+            describe_parts(engine, decoder, noun)
+        }),
+        _ if values
+            .iter()
+            .all(|value| matches!(value, Value::Vec(items) if items.is_empty())) =>
+        {
+            Desc::leaf("empty".to_string())
+        }
+        _ => Desc::leaf(format!("{noun}s: {}", summarize(values))),
+    }
+}
+
+fn describe_kinds(
+    categories: &[Engine],
+    categories_d: &[Decoder],
+    total: usize,
+    describe: impl Fn(&Engine, &Decoder) -> Desc,
+) -> Desc {
+    Desc {
+        text: format!("one of {} kinds:", categories.len()),
+        children: categories
+            .iter()
+            .zip(categories_d)
+            .map(|(engine, decoder)| {
+                let desc = describe(engine, decoder);
+                let share = profiled(decoder).1.len() * 100 / total;
+                Desc {
+                    text: format!("{share}%: {}", desc.text),
+                    children: desc.children,
+                }
+            })
+            .collect(),
+    }
+}
+
+fn describe_vec(length_d: &Decoder, item: &Engine, item_d: &Decoder, noun: &str) -> Desc {
+    let counts: Vec<usize> = profiled(length_d)
+        .1
+        .iter()
+        .map(|count| count.clone().into_usize().unwrap_or(0))
+        .collect();
+    let min_count = counts.iter().copied().min().unwrap_or(0);
+    let max_count = counts.iter().copied().max().unwrap_or(0);
+    if max_count == 0 {
+        return Desc::leaf("empty".to_string());
+    }
+    let count_text = if max_count == 1 {
+        format!("{} {noun}", range_text(min_count, max_count))
+    } else {
+        format!("{} {noun}s", range_text(min_count, max_count))
+    };
+
+    if let Some(segments) = describe_positions(&counts, item, item_d, noun) {
+        return Desc {
+            text: format!("{count_text}:"),
+            children: segments,
+        };
+    }
+
+    let item_desc = describe(item, item_d);
+    if item_desc.children.is_empty() {
+        if let Some(segments) = summarize_positions(&counts, profiled(item_d).1, noun) {
+            return Desc {
+                text: format!("{count_text}:"),
+                children: segments,
+            };
+        }
+    }
+    let each = if max_count == 1 { ":" } else { ", each" };
+    Desc {
+        text: format!("{count_text}{each} {}", item_desc.text),
+        children: item_desc.children,
+    }
+}
+
+// If items are split into categories by their position, e.g. the first line differs from the
+// rest, describes each range of positions separately
+fn describe_positions(
+    counts: &[usize],
+    item: &Engine,
+    item_d: &Decoder,
+    noun: &str,
+) -> Option<Vec<Desc>> {
+    let (
+        Engine::CategorySplit { categories, .. },
+        Decoder::CategorySplit {
+            categories: categories_d,
+            category: category_d,
+        },
+    ) = (item, profiled(item_d).0)
+    else {
+        return None;
+    };
+
+    let category_by_item = profiled(category_d).1;
+    let max_count = counts.iter().copied().max()?;
+    let mut category_by_position: Vec<Option<usize>> = vec![None; max_count];
+    let mut category_by_item = category_by_item.iter();
+    for count in counts {
+        for known in &mut category_by_position[..*count] {
+            let category = category_by_item.next()?.clone().into_usize().ok()?;
+            if *known.get_or_insert(category) != category {
+                return None;
+            }
+        }
+    }
+
+    let all_equal = counts.iter().all(|count| *count == max_count);
+    let mut segments = Vec::new();
+    let mut start = 0;
+    while start < max_count {
+        let mut end = start;
+        while end + 1 < max_count && category_by_position[end + 1] == category_by_position[start] {
+            end += 1;
+        }
+        let positions = positions_text(noun, start, end, max_count, all_equal);
+        let category = category_by_position[start]?;
+        let desc = describe(categories.get(category)?, categories_d.get(category)?);
+        segments.push(Desc {
+            text: format!("{positions}: {}", desc.text),
+            children: desc.children,
+        });
+        start = end + 1;
+    }
+    Some(segments)
+}
+
+// Items with the same summary at each position, when there are only a few distinct ones, e.g. a
+// query name followed by its arguments
+fn summarize_positions(counts: &[usize], values: &[Value], noun: &str) -> Option<Vec<Desc>> {
+    let max_count = counts.iter().copied().max()?;
+    if values.len() != counts.iter().sum::<usize>() {
+        return None;
+    }
+    let mut values_by_position = vec![Vec::new(); max_count];
+    let mut values = values.iter();
+    for count in counts {
+        for position_values in &mut values_by_position[..*count] {
+            position_values.push(values.next()?.clone());
+        }
+    }
+    let summaries: Vec<String> = values_by_position
+        .iter()
+        .map(|values| summarize(values))
+        .collect();
+
+    let all_equal = counts.iter().all(|count| *count == max_count);
+    let mut segments = Vec::new();
+    let mut start = 0;
+    while start < max_count {
+        let mut end = start;
+        while end + 1 < max_count && summaries[end + 1] == summaries[start] {
+            end += 1;
+        }
+        let positions = positions_text(noun, start, end, max_count, all_equal);
+        segments.push(Desc::leaf(format!("{positions}: {}", summaries[start])));
+        start = end + 1;
+    }
+    (2..=MAX_SEGMENTS)
+        .contains(&segments.len())
+        .then_some(segments)
+}
+
+// Positions are split into at most this many ranges with different summaries
+const MAX_SEGMENTS: usize = 4;
+
+fn positions_text(
+    noun: &str,
+    start: usize,
+    end: usize,
+    max_count: usize,
+    all_equal: bool,
+) -> String {
+    if start == end {
+        format!("{noun} {}", start + 1)
+    } else if end + 1 == max_count && !all_equal {
+        format!("{noun}s {}..", start + 1)
+    } else {
+        format!("{noun}s {}..{}", start + 1, end + 1)
+    }
+}
+
+fn range_text<T: PartialEq + std::fmt::Display>(min: T, max: T) -> String {
+    if min == max {
+        format!("{min}")
+    } else {
+        format!("{min} to {max}")
+    }
+}
+
+fn list_text<T: std::fmt::Debug>(values: &BTreeSet<T>) -> String {
+    values
+        .iter()
+        .map(|value| format!("{value:?}"))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+// Sets of up to this many distinct values are listed in full
+const MAX_LISTED: usize = 4;
+
+fn summarize(values: &[Value]) -> String {
+    if values.is_empty() {
+        return "nothing".to_string();
+    }
+
+    let ints: Option<Vec<i128>> = values
+        .iter()
+        .map(|value| match value {
+            Value::Int(n) => Some(*n),
+            Value::Str(s) => s.parse().ok().filter(|n: &i128| n.to_string() == *s),
+            _ => None,
+        })
+        .collect();
+    if let Some(ints) = ints {
+        let distinct: BTreeSet<i128> = ints.iter().copied().collect();
+        let min = distinct.first().unwrap();
+        let max = distinct.last().unwrap();
+        return if distinct.len() == 1 {
+            format!("integer {min}")
+        } else if distinct.len() <= MAX_LISTED {
+            format!("integer, one of {}", list_text(&distinct))
+        } else {
+            format!("integer in [{min}, {max}]")
+        };
+    }
+
+    let strings: Option<Vec<&str>> = values
+        .iter()
+        .map(|value| match value {
+            Value::Str(s) => Some(s.as_str()),
+            _ => None,
+        })
+        .collect();
+    if let Some(strings) = strings {
+        return summarize_strings(&strings);
+    }
+
+    let lengths: Option<Vec<usize>> = values
+        .iter()
+        .map(|value| match value {
+            Value::Bytes(bytes) => Some(bytes.len()),
+            _ => None,
+        })
+        .collect();
+    if let Some(lengths) = lengths {
+        let min = lengths.iter().min().unwrap();
+        let max = lengths.iter().max().unwrap();
+        return format!("binary data of {} bytes", range_text(min, max));
+    }
+
+    let lengths: Option<Vec<usize>> = values
+        .iter()
+        .map(|value| match value {
+            Value::Vec(items) => Some(items.len()),
+            _ => None,
+        })
+        .collect();
+    if let Some(lengths) = lengths {
+        let min = lengths.iter().min().unwrap();
+        let max = lengths.iter().max().unwrap();
+        return format!("list of {} items", range_text(min, max));
+    }
+
+    "values of mixed types".to_string()
+}
+
+fn summarize_strings(strings: &[&str]) -> String {
+    let is_decimal = |s: &&str| {
+        s.matches('.').count() == 1
+            && s.parse::<f64>().is_ok()
+            && s.chars()
+                .all(|c| c == '.' || c == '-' || c.is_ascii_digit())
+    };
+    if strings.iter().all(is_decimal) {
+        let parse = |s: &&&str| s.parse::<f64>().unwrap();
+        let min = strings
+            .iter()
+            .min_by(|a, b| parse(a).total_cmp(&parse(b)))
+            .unwrap();
+        let max = strings
+            .iter()
+            .max_by(|a, b| parse(a).total_cmp(&parse(b)))
+            .unwrap();
+        let precisions: Vec<usize> = strings
+            .iter()
+            .map(|s| s.len() - s.find('.').unwrap() - 1)
+            .collect();
+        return format!(
+            "decimal in [{min}, {max}] with {} digits after the point",
+            range_text(
+                precisions.iter().min().unwrap(),
+                precisions.iter().max().unwrap()
+            )
+        );
+    }
+
+    let distinct: BTreeSet<&str> = strings.iter().copied().collect();
+    if distinct.len() <= MAX_LISTED {
+        return if distinct.len() == 1 {
+            format!("string {}", list_text(&distinct))
+        } else {
+            format!("string, one of {}", list_text(&distinct))
+        };
+    }
+
+    let lengths: Vec<usize> = strings.iter().map(|s| s.chars().count()).collect();
+    let mut classes = Vec::new();
+    let chars: HashSet<char> = strings.iter().flat_map(|s| s.chars()).collect();
+    if chars.iter().any(|c| c.is_ascii_lowercase()) {
+        classes.push("lowercase letters".to_string());
+    }
+    if chars.iter().any(|c| c.is_ascii_uppercase()) {
+        classes.push("uppercase letters".to_string());
+    }
+    if chars.iter().any(|c| c.is_ascii_digit()) {
+        classes.push("digits".to_string());
+    }
+    let other: BTreeSet<char> = chars
+        .into_iter()
+        .filter(|c| !c.is_ascii_alphanumeric())
+        .collect();
+    if !other.is_empty() {
+        classes.push(list_text(&other));
+    }
+    format!(
+        "string of {} characters ({})",
+        range_text(lengths.iter().min().unwrap(), lengths.iter().max().unwrap()),
+        classes.join(", ")
+    )
+}
@@ -1,33 +1,25 @@
-use crate::autocompress::{autocompress_one, AutoCompressOpts};
+use crate::autocompress::{autocompress_one, choose, AutoCompressOpts};
 use crate::bits::Bits;
 use crate::compress::{Compress, Engine, MultiCompressedData};
-use crate::varint::{compress_fixint, get_bit_length};
+use crate::varint::get_bit_length;
+use std::cmp::Ordering;
 use std::collections::{BinaryHeap, HashMap, HashSet};
 
-trait Huffman {
-    fn huffman(objs: &[&Self], opts: AutoCompressOpts) -> MultiCompressedData;
-}
-
-impl<T: Compress> Huffman for T {
-    default fn huffman(objs: &[&T], opts: AutoCompressOpts) -> MultiCompressedData {
-        huffman_unordered(objs, opts)
-    }
-}
-
-impl<T: Compress + Ord> Huffman for T {
-    fn huffman(objs: &[&T], opts: AutoCompressOpts) -> MultiCompressedData {
-        let unordered = huffman_unordered(objs, opts);
-        let ordered = huffman_ordered(objs, opts);
-        if unordered.weight() < ordered.weight() {
-            unordered
-        } else {
-            ordered
-        }
-    }
-}
-
 pub fn huffman<T: Compress>(objs: &[&T], opts: AutoCompressOpts) -> MultiCompressedData {
-    <T as Huffman>::huffman(objs, opts)
+    let unordered = huffman_unordered(objs, opts);
+    if !opts.huffman_ordered() {
+        return unordered;
+    }
+    let Some(ordered) = huffman_ordered(objs, opts) else {
+        return unordered;
+    };
+    choose(
+        vec![
+            ("ordered Huffman", ordered),
+            ("canonical Huffman", unordered),
+        ],
+        opts,
+    )
 }
 
 enum Tree {
@@ -35,38 +27,51 @@ enum Tree {
     Branch(Box<Tree>, Box<Tree>),
 }
 
+// Items of equal weight are taken in the order they were created, as the order in which a binary
+// heap returns them is unspecified and may change between versions of the standard library
 struct HeapItem {
     weight: usize,
+    id: usize,
     tree: Box<Tree>,
 }
 
 impl PartialEq for HeapItem {
     fn eq(&self, other: &Self) -> bool {
-        self.weight == other.weight
+        (self.weight, self.id) == (other.weight, other.id)
     }
 }
 impl Eq for HeapItem {}
 impl PartialOrd for HeapItem {
-    fn partial_cmp(&self, other: &Self) -> Option<std::cmp::Ordering> {
-        other.weight.partial_cmp(&self.weight)
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
     }
 }
 impl Ord for HeapItem {
-    fn cmp(&self, other: &Self) -> std::cmp::Ordering {
-        other.weight.cmp(&self.weight)
+    fn cmp(&self, other: &Self) -> Ordering {
+        (other.weight, other.id).cmp(&(self.weight, self.id))
     }
 }
 
-pub fn huffman_ordered<T: Compress + Ord>(
+// None if the objects are not ordered
+pub fn huffman_ordered<T: Compress>(
     objs: &[&T],
     opts: AutoCompressOpts,
-) -> MultiCompressedData {
+) -> Option<MultiCompressedData> {
     // The alphabet is sorted, which makes it easier to encode, but the tree has to be stored
     let mut alphabet: Vec<&T> = objs.to_vec();
-    alphabet.sort();
+    let mut ordered = true;
+    alphabet.sort_by(|a, b| {
+        a.compare(b).unwrap_or_else(|| {
+            ordered = false;
+            Ordering::Equal
+        })
+    });
+    if !ordered {
+        return None;
+    }
     alphabet.dedup();
 
-    let (alphabet_representations, tree) = build_tree(&objs, &alphabet);
+    let (alphabet_representations, tree) = build_tree(objs, &alphabet);
 
     let mut tree_enc = Bits::new();
     let bit_length = get_bit_length(alphabet.len() as u128);
@@ -74,12 +79,12 @@ pub fn huffman_ordered<T: Compress + Ord>(
         match tree {
             Tree::Leaf(i) => {
                 tree_enc.push(true);
-                tree_enc.extend(&compress_fixint(*i as u128, bit_length));
+                tree_enc.push_int(*i as u128, bit_length);
             }
             Tree::Branch(a, b) => {
                 tree_enc.push(false);
-                walk(&a, tree_enc, bit_length);
-                walk(&b, tree_enc, bit_length);
+                walk(a, tree_enc, bit_length);
+                walk(b, tree_enc, bit_length);
             }
         }
     }
@@ -92,7 +97,7 @@ pub fn huffman_ordered<T: Compress + Ord>(
 
     let alphabet_compressed = autocompress_one(&alphabet, opts);
 
-    MultiCompressedData {
+    Some(MultiCompressedData {
         engine: Engine::SpecificHuffman {
             alphabet_engine: Box::new(alphabet_compressed.engine),
             alphabet_data: alphabet_compressed.binary_data,
@@ -102,13 +107,18 @@ pub fn huffman_ordered<T: Compress + Ord>(
             .iter()
             .map(|obj| alphabet_representations[alphabet_offset[obj]].clone())
             .collect(),
-    }
+    })
 }
 
 pub fn huffman_unordered<T: Compress>(objs: &[&T], opts: AutoCompressOpts) -> MultiCompressedData {
     // The alphabet is reordered, but the tree is implicit (canonical)
-    let alphabet: HashSet<&T> = objs.iter().cloned().collect();
-    let alphabet: Vec<&T> = alphabet.into_iter().collect();
+    // Objects are taken in the order of first occurrence so that ties are broken deterministically
+    let mut seen: HashSet<&T> = HashSet::new();
+    let alphabet: Vec<&T> = objs
+        .iter()
+        .cloned()
+        .filter(|obj| seen.insert(obj))
+        .collect();
 
     // Generate code lengths. What alphabet order we use doesn't matter, only counts do
     let (alphabet_representations, _) = build_tree(objs, &alphabet);
@@ -130,9 +140,6 @@ pub fn huffman_unordered<T: Compress>(objs: &[&T], opts: AutoCompressOpts) -> Mu
     let mut alphabet_representations: HashMap<&T, Bits> = HashMap::new();
     let mut code = Bits::new();
     for (i, obj) in alphabet.into_iter().enumerate() {
-        while code.len() < *lengths[i] {
-            code.push(false);
-        }
         if i > 0 {
             // Increment
             let mut cnt = 0usize;
@@ -144,6 +151,9 @@ pub fn huffman_unordered<T: Compress>(objs: &[&T], opts: AutoCompressOpts) -> Mu
                 code.push(false);
             }
         }
+        while code.len() < *lengths[i] {
+            code.push(false);
+        }
         alphabet_representations.insert(obj, code.clone());
     }
 
@@ -172,17 +182,21 @@ fn build_tree<'a, T: Compress>(objs: &[&'a T], alphabet: &[&'a T]) -> (Vec<Bits>
     for (i, count) in alphabet_counts.iter().enumerate() {
         heap.push(HeapItem {
             weight: *count,
+            id: i,
             tree: Box::new(Tree::Leaf(i)),
         });
     }
 
+    let mut next_id = alphabet.len();
     while heap.len() > 1 {
         let a = heap.pop().unwrap();
         let b = heap.pop().unwrap();
         heap.push(HeapItem {
             weight: a.weight + b.weight,
+            id: next_id,
             tree: Box::new(Tree::Branch(a.tree, b.tree)),
         });
+        next_id += 1;
     }
 
     let mut alphabet_representations = vec![Bits::new(); alphabet.len()];
@@ -195,10 +209,10 @@ fn build_tree<'a, T: Compress>(objs: &[&'a T], alphabet: &[&'a T]) -> (Vec<Bits>
             }
             Tree::Branch(a, b) => {
                 prefix.push(false);
-                walk(&a, alphabet_representations, prefix);
+                walk(a, alphabet_representations, prefix);
                 prefix.pop();
                 prefix.push(true);
-                walk(&b, alphabet_representations, prefix);
+                walk(b, alphabet_representations, prefix);
                 prefix.pop();
             }
         }
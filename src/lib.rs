@@ -0,0 +1,99 @@
+//! Domain-specific compression of competitive programming test files.
+//!
+//! The core of the crate is [`autocompress`], which searches for an efficient [`Engine`] to
+//! compress a group of objects of any [`Compress`] type: strings, integers, vectors, file
+//! contents. The resulting engine tree describes the structure of the data and is stored in
+//! front of the compressed data, so that [`Decoder`] can restore the objects.
+//!
+//! [`compress`] and [`decompress`] work with plain groups of objects, while [`pack`] and
+//! [`unpack`] produce self-describing packets of files, as used by the `taco` binary.
+
+mod answers;
+mod autocompress;
+mod bits;
+mod compress;
+mod compress_file;
+mod compress_int;
+mod compress_str;
+mod compress_vec;
+mod context_model;
+mod cpp;
+mod decompress;
+mod explain;
+mod export;
+mod format_report;
+mod huffman;
+mod lint;
+mod packet;
+mod parallel;
+mod range_coding;
+mod sample;
+mod schema;
+mod split;
+mod tune;
+mod varint;
+
+pub use autocompress::{autocompress, autocompress_one, AutoCompressOpts, SearchContext};
+pub use bits::{BitReader, Bits};
+pub use compress::{Compress, CompressedData, Engine, MultiCompressedData};
+pub use compress_file::FileContents;
+pub use cpp::packet_to_cpp;
+pub use decompress::{decode_group, DecodeError, Decoder, Value};
+pub use explain::{explain, explain_answers, explain_with_schema};
+pub use format_report::describe_format;
+pub use lint::{lint, Inconsistency};
+pub use packet::{answer_name, pack, pack_with_schema, pair_tests, unpack, Packet, MAGIC, VERSION};
+pub use sample::sample;
+pub use schema::{Schema, SchemaError};
+pub use tune::{tune, Tuning};
+
+/// Compresses a group of objects with an automatically chosen engine.
+///
+/// The engine tree is available as `engine` of the result. Use
+/// `MultiCompressedData::to_bits().to_bytes()` to serialize the engine together with the data, and
+/// [`decompress`] to restore the objects.
+pub fn compress<T: Compress>(objs: &[T], opts: AutoCompressOpts) -> MultiCompressedData {
+    let objs_refs: Vec<&T> = objs.iter().collect();
+    autocompress(&objs_refs, opts)
+}
+
+/// Compresses files that follow a schema, with an engine that stores only the values of its fields.
+/// Falls back to [`compress`] if some file is binary or doesn't follow the schema.
+pub fn compress_with_schema(
+    files: &[FileContents],
+    schema: &Schema,
+    opts: AutoCompressOpts,
+) -> MultiCompressedData {
+    let texts: Option<Vec<&String>> = files
+        .iter()
+        .map(|file| match file {
+            FileContents::Text(s) => Some(s),
+            FileContents::Binary(_) => None,
+        })
+        .collect();
+    texts
+        .and_then(|texts| schema::schema_compressed(&texts, schema, opts))
+        .unwrap_or_else(|| compress(files, opts))
+}
+
+/// Compresses answers to test inputs, one per input. The number of lines of each answer may be
+/// taken from a count in its input, in which case the inputs are needed to decode the answers, as
+/// [`unpack`] does. Otherwise this is the same as [`compress`].
+pub fn compress_answers(
+    inputs: &[FileContents],
+    answers: &[FileContents],
+    opts: AutoCompressOpts,
+) -> MultiCompressedData {
+    let inputs_refs: Vec<&FileContents> = inputs.iter().collect();
+    let answers_refs: Vec<&FileContents> = answers.iter().collect();
+    answers::answers_compressed(&inputs_refs, &answers_refs, opts)
+}
+
+/// Restores `n` objects from the bytes of a serialized [`MultiCompressedData`].
+///
+/// Objects are returned as dynamically typed [`Value`]s, as engines don't store the type of the
+/// objects they were built for. The number of objects is not stored in the data and has to be
+/// known in advance.
+pub fn decompress(bytes: &[u8], n: usize) -> Result<Vec<Value>, DecodeError> {
+    decode_group(&mut BitReader::new(&Bits::from_bytes(bytes)), n)
+}
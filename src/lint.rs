@@ -0,0 +1,298 @@
+use std::collections::HashMap;
+
+use crate::autocompress::{autocompress, AutoCompressOpts};
+use crate::bits::BitReader;
+use crate::compress::{Compress, Engine};
+use crate::compress_str::{get_string_kind, StringKind};
+use crate::decompress::{Decoder, Value};
+use crate::explain::payload_children;
+
+/// A part of an object's structure that differs from the majority of the group.
+#[derive(Clone, Debug)]
+pub struct Inconsistency {
+    /// Index of the object in the group.
+    pub object: usize,
+    /// Path to the differing node of the engine tree, e.g. `root > words > item`.
+    pub node: String,
+    pub message: String,
+}
+
+// Strings at each node of the engine tree are grouped by the same kinds that category splitting
+// uses, e.g. a line with a trailing space has an empty token, and a token with CRLF line endings is
+// not a number. This works even when the strings are deduplicated instead of split: an alphabet is
+// decoded once rather than per object, so its entries are decoded one at a time and the strings
+// under them are attributed to the objects that use them. Objects that have a kind of strings rare
+// among the others, or lack an empty string most others have, e.g. the one after the final line
+// break, are reported
+struct Node {
+    path: String,
+    // Values decoded at the node, by object
+    values: Vec<Vec<Value>>,
+}
+
+// Decodes units, i.e. objects or entries of an alphabet, one at a time and splits the values of
+// every node by unit
+fn split_by_unit(
+    engine: &Engine,
+    path: String,
+    n_units: usize,
+    mut decode_unit: impl FnMut(&mut Decoder),
+) -> Vec<Node> {
+    let mut decoder = Decoder::new_profiled(engine).expect("Failed to decode own output");
+
+    // Values each node has decoded by the end of each unit
+    let mut ends = Vec::with_capacity(n_units);
+    for _ in 0..n_units {
+        decode_unit(&mut decoder);
+        let mut counts = Vec::new();
+        value_counts(engine, &decoder, &mut counts);
+        ends.push(counts);
+    }
+
+    let mut nodes = Vec::new();
+    collect_nodes(engine, &decoder, path, &ends, &mut 0, &mut nodes);
+    nodes
+}
+
+fn collect_nodes(
+    engine: &Engine,
+    decoder: &Decoder,
+    path: String,
+    ends: &[Vec<usize>],
+    id: &mut usize,
+    nodes: &mut Vec<Node>,
+) {
+    let Decoder::Profiled { inner, values, .. } = decoder else {
+        unreachable!()
+    };
+    for (name, child_engine, child_decoder) in payload_children(engine, inner) {
+        collect_nodes(
+            child_engine,
+            child_decoder,
+            format!("{path} > {name}"),
+            ends,
+            id,
+            nodes,
+        );
+    }
+
+    let mut start = 0;
+    let by_unit: Vec<Vec<Value>> = ends
+        .iter()
+        .map(|counts| {
+            let unit_values = values[start..counts[*id]].to_vec();
+            start = counts[*id];
+            unit_values
+        })
+        .collect();
+    *id += 1;
+
+    if let Some((entries, entry_nodes)) = alphabet_nodes(engine, &path) {
+        let entry_ids: HashMap<&Value, usize> = entries.iter().zip(0..).collect();
+        for entry_node in entry_nodes {
+            nodes.push(Node {
+                path: entry_node.path,
+                values: by_unit
+                    .iter()
+                    .map(|unit_values| {
+                        unit_values
+                            .iter()
+                            .flat_map(|value| entry_node.values[entry_ids[value]].iter().cloned())
+                            .collect()
+                    })
+                    .collect(),
+            });
+        }
+    }
+    nodes.push(Node {
+        path,
+        values: by_unit,
+    });
+}
+
+// Entries of the node's alphabet and the nodes under them, except the entries themselves
+fn alphabet_nodes(engine: &Engine, path: &str) -> Option<(Vec<Value>, Vec<Node>)> {
+    let (Engine::Alphabet {
+        alphabet_engine,
+        alphabet_data,
+        ..
+    }
+    | Engine::SpecificHuffman {
+        alphabet_engine,
+        alphabet_data,
+        ..
+    }
+    | Engine::CanonicalHuffman {
+        alphabet_engine,
+        alphabet_data,
+        ..
+    }) = engine
+    else {
+        return None;
+    };
+    let Engine::Vec { length, item } = &**alphabet_engine else {
+        return None;
+    };
+
+    let mut reader = BitReader::new(alphabet_data);
+    let n_entries = Decoder::new(length)
+        .and_then(|mut decoder| decoder.decode(&mut reader))
+        .and_then(Value::into_usize)
+        .expect("Failed to decode own output");
+    let mut entries = Vec::with_capacity(n_entries);
+    let entries_path = format!("{path} > alphabet");
+    let mut nodes = split_by_unit(item, entries_path.clone(), n_entries, |decoder| {
+        entries.push(
+            decoder
+                .decode(&mut reader)
+                .expect("Failed to decode own output"),
+        );
+    });
+    nodes.retain(|node| node.path != entries_path);
+    Some((entries, nodes))
+}
+
+// Must visit nodes in the same order as collect_nodes
+fn value_counts(engine: &Engine, decoder: &Decoder, counts: &mut Vec<usize>) {
+    let Decoder::Profiled { inner, values, .. } = decoder else {
+        unreachable!()
+    };
+    for (_, child_engine, child_decoder) in payload_children(engine, inner) {
+        value_counts(child_engine, child_decoder, counts);
+    }
+    counts.push(values.len());
+}
+
+// Negative integers are not a different kind of data, even though they are split from positive ones
+fn lint_kind(s: &str) -> StringKind {
+    if s.parse::<i128>().is_ok_and(|n| n.to_string() == s) {
+        StringKind::DecimalNumber
+    } else {
+        get_string_kind(s)
+    }
+}
+
+// Objects are only reported if they are rare enough: at most one in this many
+const RARE: usize = 4;
+
+fn kind_name(kind: StringKind) -> &'static str {
+    match kind {
+        StringKind::Empty => "an empty string",
+        StringKind::DecimalNumber => "an integer",
+        StringKind::ExtendedDecimalNumber => "a number",
+        StringKind::Latin => "a word",
+        StringKind::LatinNumeric => "an alphanumeric word",
+        StringKind::Text => "ASCII text",
+        StringKind::Generic => "non-ASCII text",
+    }
+}
+
+const MAX_EXAMPLE_LENGTH: usize = 40;
+
+fn example(s: &str) -> String {
+    if s.chars().count() > MAX_EXAMPLE_LENGTH {
+        let prefix: String = s.chars().take(MAX_EXAMPLE_LENGTH).collect();
+        format!("{prefix:?}...")
+    } else {
+        format!("{s:?}")
+    }
+}
+
+/// Compresses a group of objects and finds objects whose inferred structure differs from the
+/// majority, such as a test with CRLF line endings or a missing final line break in a test pack.
+pub fn lint<T: Compress>(objs: &[T], opts: AutoCompressOpts) -> Vec<Inconsistency> {
+    let objs_refs: Vec<&T> = objs.iter().collect();
+    let data = autocompress(&objs_refs, opts);
+    let mut chunks = data.binary_data.iter();
+    let nodes = split_by_unit(
+        &data.engine,
+        "root".to_string(),
+        data.binary_data.len(),
+        |decoder| {
+            decoder
+                .decode(&mut BitReader::new(chunks.next().unwrap()))
+                .expect("Failed to decode own output");
+        },
+    );
+
+    let mut inconsistencies = Vec::new();
+    for node in &nodes {
+        let Some(strings) = node
+            .values
+            .iter()
+            .map(|values| {
+                values
+                    .iter()
+                    .map(|value| match value {
+                        Value::Str(s) => Some(s.as_str()),
+                        _ => None,
+                    })
+                    .collect::<Option<Vec<&str>>>()
+            })
+            .collect::<Option<Vec<_>>>()
+        else {
+            continue;
+        };
+
+        // Strings of each object that reaches the node
+        let mut kinds = Vec::new();
+        let mut strings_by_object = Vec::new();
+        for (object, object_strings) in strings.iter().enumerate() {
+            if !object_strings.is_empty() {
+                strings_by_object.push((object, object_strings));
+            }
+            for s in object_strings {
+                if !kinds.contains(&lint_kind(s)) {
+                    kinds.push(lint_kind(s));
+                }
+            }
+        }
+        // With fewer objects, there is no majority to compare with
+        let total = strings_by_object.len();
+        if kinds.len() < 2 || total < 3 {
+            continue;
+        }
+
+        for kind in kinds {
+            let (users, others): (Vec<_>, Vec<_>) =
+                strings_by_object.iter().partition(|(_, object_strings)| {
+                    object_strings.iter().any(|s| lint_kind(s) == kind)
+                });
+
+            if users.len() * RARE <= total {
+                for (object, object_strings) in &users {
+                    let s = object_strings
+                        .iter()
+                        .find(|s| lint_kind(s) == kind)
+                        .unwrap();
+                    inconsistencies.push(Inconsistency {
+                        object: *object,
+                        node: node.path.clone(),
+                        message: format!(
+                            "has {}, e.g. {}, unlike {} of {total} objects",
+                            kind_name(kind),
+                            example(s),
+                            others.len(),
+                        ),
+                    });
+                }
+            } else if kind == StringKind::Empty
+                && !others.is_empty()
+                && others.len() * RARE <= total
+            {
+                for (object, _) in &others {
+                    inconsistencies.push(Inconsistency {
+                        object: *object,
+                        node: node.path.clone(),
+                        message: format!(
+                            "has no empty string, unlike {} of {total} objects",
+                            users.len()
+                        ),
+                    });
+                }
+            }
+        }
+    }
+    inconsistencies.sort_by_key(|inconsistency| inconsistency.object);
+    inconsistencies
+}
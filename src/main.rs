@@ -1,43 +1,683 @@
-#![feature(specialization)]
+use std::fmt::Write as _;
+use std::io::{Read, Write};
+use std::path::{Path, PathBuf};
+use std::process::ExitCode;
+use std::time::{Duration, Instant};
 
-use std::io::Write;
+use taco::{
+    answer_name, compress, compress_answers, compress_with_schema, describe_format, explain,
+    explain_answers, explain_with_schema, lint, pack, pack_with_schema, packet_to_cpp, pair_tests,
+    sample, tune, unpack, AutoCompressOpts, DecodeError, FileContents, MultiCompressedData, Packet,
+    Schema, Value,
+};
 
-mod autocompress;
-mod bits;
-mod compress;
-mod compress_int;
-mod compress_str;
-mod compress_vec;
-mod huffman;
-mod split;
-mod varint;
+const USAGE: &str = "\
+Usage: taco [compress] [<options>] <file>... | -r <dir> | -p <dir>
+       taco decompress [-o <path>] <packet> [<output>...]
+       taco explain [<options>] <file>... | -r <dir> | -p <dir>
+       taco bench [<options>] <file>... | -r <dir> | -p <dir>
+       taco tune [<options>] <dir>...
 
-use autocompress::{autocompress, AutoCompressOpts};
+Commands:
+  compress      Compress files into a packet (the default). With -r, all files in the directory
+                are compressed along with their paths relative to it. With -p, the directory is a
+                test package: answers, such as 01.a for 01 or 1.out for 1.in, are compressed
+                separately from the inputs, and the schema only applies to the inputs.
+  decompress    Restore files from a packet, also available as -d. Packets with file names are
+                extracted to the directory given by -o or by the only output, the current
+                directory by default. Other packets are written to the outputs, one file each,
+                or one after another to -o or stdout. Answers are only extracted along with the
+                names of their inputs.
+  explain       Print how the files would be compressed, same as compress -e.
+  bench         Compress and decompress the files, verify the result and print sizes and times.
+  tune          Search the thresholds below for the values that make the packets of the test
+                packages in the directories smallest, starting from the ones passed, and print
+                them as options. This compresses the packages many times over.
 
-fn main() {
-    let mut args = std::env::args();
-    args.next();
+A file or packet named - is read from stdin, and output goes to stdout unless -o is given.
 
-    let mut strings = Vec::new();
-    while let Some(path) = args.next() {
-        let s = std::fs::read_to_string(path).expect("Failed to read file");
-        strings.push(s);
+Options:
+  -o <path>                    Write the output to a file
+  -l, --level <level>          Strategies to try, from 0 (fastest) to 6 (the default)
+  -t, --time-limit <seconds>   Stop trying new strategies after this time
+  -s, --schema <schema>        Input format of the files, e.g. \"n m / n ints / m pairs\"
+  --no-dedup-and-categories    Don't deduplicate equal objects or split them into categories,
+                               except in nested groups such as alphabets and run values
+  --no-stateful                Don't compress whole groups as a single list, except in nested
+                               groups such as alphabets and run values
+  --category-depth <depth>     Act as if objects have gone through this many category splits
+
+Thresholds of the search, in percent of the number of objects:
+  --dedup-percent <percent>    Deduplicate if distinct objects are fewer than this (default 50)
+  --dedup-slack <count>        ...plus this many (default 3)
+  --split-percent <percent>    Split into categories if they are fewer than this (default 50)
+  --rle-percent <percent>      Run-length encode if runs are fewer than this (default 50)
+  --separator-percent <percent>
+                               Split text by a separator if it occurs at least this many times
+                               (default 100)
+
+Output of compress, instead of the packet:
+  -e                           How the files would be compressed
+  -f                           Format of the files inferred from the compression
+  -c                           Files whose structure differs from the rest; exits with 1 if any
+  -x json|dot|cpp              Engine trees as JSON or DOT, or a C++ program printing the files
+  -g <seed>                    A random file with the same structure as the files
+
+Errors are reported to stderr, with exit code 2.
+";
+
+type CliResult<T> = Result<T, String>;
+
+fn main() -> ExitCode {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    match run(&args) {
+        Ok(code) => code,
+        Err(err) => {
+            eprintln!("taco: {err}");
+            ExitCode::from(2)
+        }
+    }
+}
+
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum Command {
+    Compress,
+    Decompress,
+    Explain,
+    Bench,
+    Tune,
+}
+
+fn run(args: &[String]) -> CliResult<ExitCode> {
+    let (command, args) = match args.first().map(|arg| arg.as_str()) {
+        Some("compress") => (Command::Compress, &args[1..]),
+        Some("decompress" | "-d") => (Command::Decompress, &args[1..]),
+        Some("explain") => (Command::Explain, &args[1..]),
+        Some("bench") => (Command::Bench, &args[1..]),
+        Some("tune") => (Command::Tune, &args[1..]),
+        Some("help" | "-h" | "--help") => {
+            print!("{USAGE}");
+            return Ok(ExitCode::SUCCESS);
+        }
+        None => return Err(format!("no input files\n\n{USAGE}")),
+        _ => (Command::Compress, args),
+    };
+    let args = parse_args(command, args)?;
+    match command {
+        Command::Decompress => {
+            decompress_main(&args)?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Bench => {
+            let packet = read_source(&args)?;
+            let report = bench(&packet, &args)?;
+            write_output(args.output_path.as_deref(), report.as_bytes())?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Tune => {
+            let report = tune_main(&args)?;
+            write_output(args.output_path.as_deref(), report.as_bytes())?;
+            Ok(ExitCode::SUCCESS)
+        }
+        Command::Compress | Command::Explain => {
+            let packet = read_source(&args)?;
+            write_packet(&packet, &args)
+        }
     }
+}
+
+#[derive(Clone, Copy)]
+enum Output {
+    Packet,
+    Explain,
+    Format,
+    Lint,
+    Json,
+    Dot,
+    Cpp,
+    Sample(u64),
+}
 
-    let strings_refs: Vec<&String> = strings.iter().collect();
+enum Source {
+    Files,
+    Dir(String),
+    Package(String),
+}
 
-    let compressed = autocompress(&strings_refs, AutoCompressOpts::default());
+struct Args {
+    opts: AutoCompressOpts<'static>,
+    schema: Option<Schema>,
+    output: Output,
+    output_path: Option<String>,
+    source: Source,
+    paths: Vec<String>,
+}
 
-    let mut result = compressed.engine.to_bits();
-    for chunk in compressed.binary_data {
-        result.extend(&chunk);
+fn parse_value<T: std::str::FromStr>(value: &str, what: &str) -> CliResult<T> {
+    value
+        .parse()
+        .map_err(|_| format!("invalid {what}: {value:?}"))
+}
+
+fn parse_args(command: Command, args: &[String]) -> CliResult<Args> {
+    let mut parsed = Args {
+        opts: AutoCompressOpts::default(),
+        schema: None,
+        output: match command {
+            Command::Explain => Output::Explain,
+            _ => Output::Packet,
+        },
+        output_path: None,
+        source: Source::Files,
+        paths: Vec::new(),
+    };
+    let mut args = args.iter();
+    let mut options_ended = false;
+    while let Some(arg) = args.next() {
+        if options_ended || arg == "-" || !arg.starts_with('-') {
+            parsed.paths.push(arg.clone());
+            continue;
+        }
+        if arg == "--" {
+            options_ended = true;
+            continue;
+        }
+
+        let flag = arg.as_str();
+        let takes_value = matches!(
+            flag,
+            "-o" | "-l"
+                | "--level"
+                | "-t"
+                | "--time-limit"
+                | "-s"
+                | "--schema"
+                | "--category-depth"
+                | "--dedup-percent"
+                | "--dedup-slack"
+                | "--split-percent"
+                | "--rle-percent"
+                | "--separator-percent"
+                | "-x"
+                | "-g"
+                | "-r"
+                | "-p"
+        );
+        let value = if takes_value {
+            args.next()
+                .ok_or_else(|| format!("option {flag} requires a value"))?
+                .as_str()
+        } else {
+            ""
+        };
+        let allowed = match flag {
+            "-o" => true,
+            "-e" | "-f" | "-c" | "-x" | "-g" => command == Command::Compress,
+            "-l"
+            | "--level"
+            | "--category-depth"
+            | "--no-dedup-and-categories"
+            | "--no-stateful"
+            | "--dedup-percent"
+            | "--dedup-slack"
+            | "--split-percent"
+            | "--rle-percent"
+            | "--separator-percent" => command != Command::Decompress,
+            // Tuning compares sizes, which must not depend on timing, and only takes packages
+            "-t" | "--time-limit" | "-s" | "--schema" | "-r" | "-p" => {
+                command != Command::Decompress && command != Command::Tune
+            }
+            _ => return Err(format!("unknown option {flag}, see taco help")),
+        };
+        if !allowed {
+            return Err(format!("option {flag} can't be used with this command"));
+        }
+
+        match flag {
+            "-o" => parsed.output_path = Some(value.to_string()),
+            "-l" | "--level" => {
+                parsed.opts.level = value
+                    .parse()
+                    .ok()
+                    .filter(|level| *level <= AutoCompressOpts::MAX_LEVEL)
+                    .ok_or_else(|| {
+                        format!(
+                            "compression level must be between 0 and {}",
+                            AutoCompressOpts::MAX_LEVEL
+                        )
+                    })?;
+            }
+            "-t" | "--time-limit" => {
+                let seconds: f64 = parse_value(value, "time limit")?;
+                let limit = Duration::try_from_secs_f64(seconds)
+                    .map_err(|_| format!("invalid time limit: {value:?}"))?;
+                parsed.opts.deadline = Some(Instant::now() + limit);
+            }
+            "-s" | "--schema" => {
+                parsed.schema = Some(Schema::parse(value).map_err(|err| err.to_string())?);
+            }
+            "--no-dedup-and-categories" => parsed.opts.enable_dedup_and_categories = false,
+            "--no-stateful" => parsed.opts.enable_stateful = false,
+            "--category-depth" => {
+                parsed.opts.category_depth = parse_value(value, "category depth")?;
+            }
+            "--dedup-percent" => parsed.opts.dedup_percent = parse_value(value, "percentage")?,
+            "--dedup-slack" => parsed.opts.dedup_slack = parse_value(value, "count")?,
+            "--split-percent" => parsed.opts.split_percent = parse_value(value, "percentage")?,
+            "--rle-percent" => parsed.opts.rle_percent = parse_value(value, "percentage")?,
+            "--separator-percent" => {
+                parsed.opts.separator_percent = parse_value(value, "percentage")?;
+            }
+            "-e" => parsed.output = Output::Explain,
+            "-f" => parsed.output = Output::Format,
+            "-c" => parsed.output = Output::Lint,
+            "-x" => {
+                parsed.output = match value {
+                    "json" => Output::Json,
+                    "dot" => Output::Dot,
+                    "cpp" => Output::Cpp,
+                    _ => return Err("export format must be json, dot or cpp".to_string()),
+                };
+            }
+            "-g" => parsed.output = Output::Sample(parse_value(value, "seed")?),
+            "-r" => parsed.source = Source::Dir(value.to_string()),
+            "-p" => parsed.source = Source::Package(value.to_string()),
+            _ => unreachable!(),
+        }
     }
 
-    // eprintln!("{:?}", compressed.engine);
-    // println!("{:?}", result.to_bytes().len());
-    // println!("{:?}", compressed.weight());
+    // The format is given by the schema rather than inferred, and schema fields hold numbers that
+    // have no structure to check
+    if parsed.schema.is_some() && matches!(parsed.output, Output::Format | Output::Lint) {
+        return Err("-s can't be used with -f or -c".to_string());
+    }
+    if parsed.paths.iter().filter(|path| *path == "-").count() > 1 {
+        return Err("stdin can only be read once".to_string());
+    }
+    match (&parsed.source, command) {
+        (_, Command::Decompress) if parsed.paths.is_empty() => {
+            Err("packet path is missing".to_string())
+        }
+        (_, Command::Tune) if parsed.paths.is_empty() => {
+            Err("no test package directories".to_string())
+        }
+        (Source::Files, _) if parsed.paths.is_empty() => Err(format!("no input files\n\n{USAGE}")),
+        (Source::Dir(_) | Source::Package(_), _) if !parsed.paths.is_empty() => {
+            Err("files can't be passed along with a directory to compress".to_string())
+        }
+        _ => Ok(parsed),
+    }
+}
 
-    std::io::stdout()
-        .write(&result.to_bytes())
-        .expect("Failed to write to stdout");
+fn read_input(path: &str) -> CliResult<Vec<u8>> {
+    if path == "-" {
+        let mut bytes = Vec::new();
+        std::io::stdin()
+            .read_to_end(&mut bytes)
+            .map_err(|err| format!("failed to read stdin: {err}"))?;
+        Ok(bytes)
+    } else {
+        std::fs::read(path).map_err(|err| format!("failed to read {path}: {err}"))
+    }
+}
+
+fn write_output(path: Option<&str>, bytes: &[u8]) -> CliResult<()> {
+    match path {
+        None | Some("-") => std::io::stdout()
+            .write_all(bytes)
+            .map_err(|err| format!("failed to write to stdout: {err}")),
+        Some(path) => {
+            std::fs::write(path, bytes).map_err(|err| format!("failed to write {path}: {err}"))
+        }
+    }
+}
+
+fn read_source(args: &Args) -> CliResult<Packet> {
+    let dir = match &args.source {
+        Source::Files => {
+            let files = args
+                .paths
+                .iter()
+                .map(|path| read_input(path).map(FileContents::from_bytes))
+                .collect::<CliResult<_>>()?;
+            return Ok(Packet {
+                names: None,
+                files,
+                answers: None,
+            });
+        }
+        Source::Dir(dir) | Source::Package(dir) => dir,
+    };
+
+    let files = read_dir(dir)?;
+    Ok(match args.source {
+        Source::Package(_) => pair_tests(files),
+        _ => {
+            let (names, files) = files.into_iter().unzip();
+            Packet {
+                names: Some(names),
+                files,
+                answers: None,
+            }
+        }
+    })
+}
+
+fn read_dir(dir: &str) -> CliResult<Vec<(String, FileContents)>> {
+    let mut paths = Vec::new();
+    collect_files(Path::new(dir), "", &mut paths)?;
+    paths.sort();
+    paths
+        .into_iter()
+        .map(|(name, path)| {
+            let bytes = std::fs::read(&path)
+                .map_err(|err| format!("failed to read {}: {err}", path.display()))?;
+            Ok((name, FileContents::from_bytes(bytes)))
+        })
+        .collect()
+}
+
+fn write_packet(packet: &Packet, args: &Args) -> CliResult<ExitCode> {
+    let opts = args.opts;
+    let schema = args.schema.as_ref();
+    let mut out = String::new();
+    let mut code = ExitCode::SUCCESS;
+    match args.output {
+        Output::Packet => {
+            let bytes = pack_packet(packet, opts, schema);
+            write_output(args.output_path.as_deref(), &bytes)?;
+            return Ok(code);
+        }
+        Output::Cpp => {
+            // The program only prints the files, and answers would be lost without notice
+            if packet.answers.is_some() {
+                return Err("C++ output can't include the answers of a test package".to_string());
+            }
+            out = packet_to_cpp(&pack_packet(packet, opts, schema))
+                .map_err(|err| format!("failed to read own packet: {err}"))?;
+        }
+        Output::Explain => {
+            let explained = |result: Result<String, DecodeError>| {
+                result.map_err(|err| format!("failed to read own output: {err}"))
+            };
+            if let Some(names) = &packet.names {
+                write!(out, "Names: {}", explained(explain(names, opts))?).unwrap();
+            }
+            let files = match schema {
+                Some(schema) => explain_with_schema(&packet.files, schema, opts),
+                None => explain(&packet.files, opts),
+            };
+            write!(out, "Files: {}", explained(files)?).unwrap();
+            if let Some((_, inputs, answers)) = answer_files(packet) {
+                let answers = explain_answers(&inputs, &answers, opts);
+                write!(out, "Answers: {}", explained(answers)?).unwrap();
+            }
+        }
+        Output::Format => {
+            if let Some(names) = &packet.names {
+                write!(out, "Names: {}", describe_format(names, opts)).unwrap();
+            }
+            write!(out, "Files: {}", describe_format(&packet.files, opts)).unwrap();
+            if let Some((_, _, answers)) = answer_files(packet) {
+                write!(out, "Answers: {}", describe_format(&answers, opts)).unwrap();
+            }
+        }
+        Output::Lint => {
+            let names: Vec<String> = match &packet.names {
+                Some(names) => names.clone(),
+                None => (1..=packet.files.len())
+                    .map(|i| format!("file #{i}"))
+                    .collect(),
+            };
+            // Inputs and answers have unrelated formats, so they are checked separately
+            let mut groups = vec![(names, packet.files.clone())];
+            groups.extend(answer_files(packet).map(|(names, _, answers)| (names, answers)));
+            for (names, files) in groups {
+                for inconsistency in lint(&files, opts) {
+                    let file = &names[inconsistency.object];
+                    writeln!(
+                        out,
+                        "{file}: {}: {}",
+                        inconsistency.node, inconsistency.message
+                    )
+                    .unwrap();
+                    code = ExitCode::FAILURE;
+                }
+            }
+        }
+        Output::Json => {
+            out.push('{');
+            if let Some(names) = &packet.names {
+                write!(out, "\"names\":{},", compress(names, opts).engine.to_json()).unwrap();
+            }
+            write!(
+                out,
+                "\"files\":{}",
+                compress_files(&packet.files, opts, schema).engine.to_json()
+            )
+            .unwrap();
+            if let Some((_, inputs, answers)) = answer_files(packet) {
+                let engine = compress_answers(&inputs, &answers, opts).engine;
+                write!(out, ",\"answers\":{}", engine.to_json()).unwrap();
+            }
+            out.push_str("}\n");
+        }
+        Output::Sample(seed) => {
+            let data = compress_files(&packet.files, opts, schema);
+            let file = sample(&data, seed, 1)
+                .map_err(|err| format!("failed to generate a file: {err}"))?
+                .remove(0);
+            let bytes = match file {
+                Value::Str(s) => s.into_bytes(),
+                Value::Bytes(bytes) => bytes,
+                _ => return Err("generated file is neither text nor binary".to_string()),
+            };
+            write_output(args.output_path.as_deref(), &bytes)?;
+            return Ok(code);
+        }
+        Output::Dot => {
+            // Each group is a separate graph
+            if let Some(names) = &packet.names {
+                out.push_str(&compress(names, opts).engine.to_dot());
+            }
+            out.push_str(&compress_files(&packet.files, opts, schema).engine.to_dot());
+            if let Some((_, inputs, answers)) = answer_files(packet) {
+                out.push_str(&compress_answers(&inputs, &answers, opts).engine.to_dot());
+            }
+        }
+    }
+    write_output(args.output_path.as_deref(), out.as_bytes())?;
+    Ok(code)
+}
+
+// Names of the answers that are present, their inputs and their contents, if the files are tests
+type AnswerFiles = (Vec<String>, Vec<FileContents>, Vec<FileContents>);
+
+fn answer_files(packet: &Packet) -> Option<AnswerFiles> {
+    let answers = packet.answers.as_ref()?;
+    let mut names = Vec::new();
+    let mut inputs = Vec::new();
+    let mut files = Vec::new();
+    for (i, answer) in answers.iter().enumerate() {
+        if let Some(answer) = answer {
+            names.push(match &packet.names {
+                Some(input_names) => answer_name(&input_names[i]),
+                None => format!("answer #{}", i + 1),
+            });
+            inputs.push(packet.files[i].clone());
+            files.push(answer.clone());
+        }
+    }
+    Some((names, inputs, files))
+}
+
+fn pack_packet(packet: &Packet, opts: AutoCompressOpts, schema: Option<&Schema>) -> Vec<u8> {
+    match schema {
+        Some(schema) => pack_with_schema(packet, schema, opts),
+        None => pack(packet, opts),
+    }
+}
+
+fn compress_files(
+    files: &[FileContents],
+    opts: AutoCompressOpts,
+    schema: Option<&Schema>,
+) -> MultiCompressedData {
+    match schema {
+        Some(schema) => compress_with_schema(files, schema, opts),
+        None => compress(files, opts),
+    }
+}
+
+// Times a round trip through a packet and checks that everything is restored
+fn bench(packet: &Packet, args: &Args) -> CliResult<String> {
+    let answers = packet.answers.iter().flatten().flatten();
+    let n_answers = answers.clone().count();
+    let raw_size: usize = packet
+        .files
+        .iter()
+        .chain(answers)
+        .map(|file| file.as_bytes().len())
+        .sum();
+
+    let start = Instant::now();
+    let bytes = pack_packet(packet, args.opts, args.schema.as_ref());
+    let compression_time = start.elapsed();
+    let start = Instant::now();
+    let restored = unpack(&bytes).map_err(|err| format!("failed to read own packet: {err}"))?;
+    let decompression_time = start.elapsed();
+    if restored.names != packet.names
+        || restored.files != packet.files
+        || restored.answers != packet.answers
+    {
+        return Err("restored files differ from the original ones".to_string());
+    }
+
+    let mut out = String::new();
+    write!(out, "{} files", packet.files.len()).unwrap();
+    if n_answers > 0 {
+        write!(out, " and {n_answers} answers").unwrap();
+    }
+    writeln!(out, ", {raw_size} bytes").unwrap();
+    writeln!(
+        out,
+        "packet: {} bytes, {:.2}% of the input",
+        bytes.len(),
+        bytes.len() as f64 * 100.0 / raw_size.max(1) as f64
+    )
+    .unwrap();
+    writeln!(out, "compression: {:.3} s", compression_time.as_secs_f64()).unwrap();
+    writeln!(
+        out,
+        "decompression: {:.3} s, verified",
+        decompression_time.as_secs_f64()
+    )
+    .unwrap();
+    Ok(out)
+}
+
+fn tune_main(args: &Args) -> CliResult<String> {
+    let corpus = args
+        .paths
+        .iter()
+        .map(|dir| read_dir(dir).map(pair_tests))
+        .collect::<CliResult<Vec<_>>>()?;
+    let tuning = tune(&corpus, args.opts);
+    let opts = tuning.opts;
+    Ok(format!(
+        "total size: {} -> {} bytes\n--dedup-percent {} --dedup-slack {} --split-percent {} \
+         --rle-percent {} --separator-percent {}\n",
+        tuning.initial_size,
+        tuning.size,
+        opts.dedup_percent,
+        opts.dedup_slack,
+        opts.split_percent,
+        opts.rle_percent,
+        opts.separator_percent
+    ))
+}
+
+fn collect_files(dir: &Path, prefix: &str, paths: &mut Vec<(String, PathBuf)>) -> CliResult<()> {
+    let read_error = |err| format!("failed to read directory {}: {err}", dir.display());
+    for entry in std::fs::read_dir(dir).map_err(read_error)? {
+        let entry = entry.map_err(read_error)?;
+        let name = entry
+            .file_name()
+            .into_string()
+            .map_err(|name| format!("file name is not UTF-8: {name:?}"))?;
+        let name = format!("{prefix}{name}");
+        if entry.file_type().map_err(read_error)?.is_dir() {
+            collect_files(&entry.path(), &format!("{name}/"), paths)?;
+        } else {
+            paths.push((name, entry.path()));
+        }
+    }
+    Ok(())
+}
+
+fn decompress_main(args: &Args) -> CliResult<()> {
+    let packet_path = &args.paths[0];
+    let output_paths = &args.paths[1..];
+
+    let packet = read_input(packet_path)?;
+    let packet = unpack(&packet).map_err(|err| format!("failed to decompress packet: {err}"))?;
+    let files = packet.files;
+
+    if let Some(names) = packet.names {
+        let dir = match (args.output_path.as_deref(), output_paths) {
+            (None, []) => ".",
+            (Some(dir), []) => dir,
+            (None, [dir]) => dir.as_str(),
+            _ => {
+                return Err(
+                    "packet stores file names, so only the output directory can be passed"
+                        .to_string(),
+                )
+            }
+        };
+        let dir = Path::new(dir);
+        let mut named_files: Vec<(String, FileContents)> = Vec::new();
+        for (i, answer) in packet.answers.into_iter().flatten().enumerate() {
+            if let Some(answer) = answer {
+                named_files.push((answer_name(&names[i]), answer));
+            }
+        }
+        named_files.splice(0..0, names.into_iter().zip(files));
+        for (name, file) in named_files {
+            // Don't let a malicious packet write outside the output directory
+            if name
+                .split('/')
+                .any(|component| component.is_empty() || component == "." || component == "..")
+            {
+                return Err(format!("invalid file name in packet: {name:?}"));
+            }
+            let path = dir.join(name);
+            std::fs::create_dir_all(path.parent().unwrap())
+                .map_err(|err| format!("failed to create directory: {err}"))?;
+            std::fs::write(&path, file.as_bytes())
+                .map_err(|err| format!("failed to write {}: {err}", path.display()))?;
+        }
+    } else if output_paths.is_empty() {
+        let contents: Vec<u8> = files
+            .iter()
+            .flat_map(|file| file.as_bytes())
+            .copied()
+            .collect();
+        write_output(args.output_path.as_deref(), &contents)?;
+    } else {
+        if args.output_path.is_some() {
+            return Err("-o can't be passed along with output paths".to_string());
+        }
+        if output_paths.len() != files.len() {
+            return Err(format!(
+                "packet contains {} files, but {} output paths were passed",
+                files.len(),
+                output_paths.len()
+            ));
+        }
+        for (path, file) in output_paths.iter().zip(files) {
+            write_output(Some(path), file.as_bytes())?;
+        }
+    }
+    Ok(())
 }
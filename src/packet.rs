@@ -0,0 +1,346 @@
+use crate::answers::answers_compressed;
+use crate::autocompress::AutoCompressOpts;
+use crate::bits::{BitReader, Bits};
+use crate::compress::{Compress, Engine, MultiCompressedData};
+use crate::compress_file::FileContents;
+use crate::decompress::{decode_group, DecodeError, Decoder, Value};
+use crate::schema::Schema;
+use crate::varint::{compress_varuint, decompress_varuint};
+use std::collections::HashMap;
+
+// Packet layout:
+// - magic bytes "TACO",
+// - format version, 1 byte,
+// - payload length in bits, 8 bytes, little-endian,
+// - payload, padded with zeroes to a byte:
+//   - number of files,
+//   - whether file names are stored, 1 bit,
+//   - whether the files are tests with answers, 1 bit,
+//   - engine and binary data of each file name, if stored,
+//   - engine and binary data of each file,
+//   - if the files are tests, whether each of them has an answer, 1 bit each, followed by the
+//     engine and binary data of each answer, if there are any,
+// - CRC-32 of each file, then of each answer, 4 bytes each, little-endian.
+/// Magic bytes at the start of every packet.
+pub const MAGIC: &[u8; 4] = b"TACO";
+/// Version of the packet format written by [`pack`].
+pub const VERSION: u8 = 4;
+
+const HEADER_LENGTH: usize = MAGIC.len() + 1 + 8;
+
+/// A set of files stored in a single packet.
+#[derive(Clone, PartialEq, Eq, Debug)]
+pub struct Packet {
+    /// Relative paths of the files, with '/' as separator. If absent, only the order of files is
+    /// preserved.
+    pub names: Option<Vec<String>>,
+    pub files: Vec<FileContents>,
+    /// Answers to the files, if they are test inputs, in the same order. Inputs and answers are
+    /// compressed as separate groups, as their formats are usually unrelated, but the number of
+    /// lines of each answer may be taken from a count in its input.
+    pub answers: Option<Vec<Option<FileContents>>>,
+}
+
+/// Name of the answer file of a test: `01.a` for `01`, as in Polygon packages, and `1.out` for
+/// `1.in`.
+pub fn answer_name(input: &str) -> String {
+    match input.strip_suffix(".in") {
+        Some(stem) => format!("{stem}.out"),
+        None => format!("{input}.a"),
+    }
+}
+
+/// Builds a packet of a test package, where files named by [`answer_name`] after another file
+/// are answers to it, and all other files are inputs.
+pub fn pair_tests(mut files: Vec<(String, FileContents)>) -> Packet {
+    files.sort_by(|(a, _), (b, _)| a.cmp(b));
+    let index: HashMap<&str, usize> = files
+        .iter()
+        .enumerate()
+        .map(|(i, (name, _))| (name.as_str(), i))
+        .collect();
+    // The answer name sorts after the name of its input, so answers are marked before they are met
+    let mut answer_of = vec![None; files.len()];
+    let mut is_answer = vec![false; files.len()];
+    for (i, (name, _)) in files.iter().enumerate() {
+        if !is_answer[i] {
+            answer_of[i] = index.get(answer_name(name).as_str()).copied();
+            if let Some(j) = answer_of[i] {
+                is_answer[j] = true;
+            }
+        }
+    }
+
+    let mut files: Vec<Option<(String, FileContents)>> = files.into_iter().map(Some).collect();
+    let mut names = Vec::new();
+    let mut inputs = Vec::new();
+    let mut answers = Vec::new();
+    for i in 0..files.len() {
+        if is_answer[i] {
+            continue;
+        }
+        let (name, file) = files[i].take().unwrap();
+        names.push(name);
+        inputs.push(file);
+        answers.push(answer_of[i].map(|j| files[j].take().unwrap().1));
+    }
+    let has_answers = answers.iter().any(Option::is_some);
+    Packet {
+        names: Some(names),
+        files: inputs,
+        answers: has_answers.then_some(answers),
+    }
+}
+
+// Whether a file is text, and its bytes, as stored by Engine::Stored
+fn raw_file(file: &FileContents) -> (bool, &[u8]) {
+    (matches!(file, FileContents::Text(_)), file.as_bytes())
+}
+
+fn stored(objs: &[(bool, &[u8])]) -> MultiCompressedData {
+    let binary_data = objs
+        .iter()
+        .map(|(text, bytes)| {
+            let mut bits = Bits::new();
+            bits.push(*text);
+            bits.extend(&compress_varuint(bytes.len() as u128));
+            for byte in *bytes {
+                bits.push_int(*byte as u128, 8);
+            }
+            bits
+        })
+        .collect();
+    MultiCompressedData {
+        engine: Engine::Stored,
+        binary_data,
+    }
+}
+
+// Every group goes through a final check: if storing the objects as they are is smaller than the
+// compressed data, they are stored instead. This bounds the size of a packet by the size of the
+// files, plus a few bits per file and the fixed parts of the format
+fn push_group(payload: &mut Bits, data: MultiCompressedData, raw: &[(bool, &[u8])]) {
+    let stored = stored(raw);
+    if stored.weight() < data.weight() {
+        payload.extend(&stored.to_bits());
+    } else {
+        payload.extend(&data.to_bits());
+    }
+}
+
+/// Compresses files into a packet.
+pub fn pack(packet: &Packet, opts: AutoCompressOpts) -> Vec<u8> {
+    pack_impl(packet, None, opts)
+}
+
+/// Compresses files into a packet, following the input format described by the schema instead of
+/// guessing it. If some file doesn't follow the schema, this is the same as [`pack`].
+pub fn pack_with_schema(packet: &Packet, schema: &Schema, opts: AutoCompressOpts) -> Vec<u8> {
+    pack_impl(packet, Some(schema), opts)
+}
+
+fn pack_impl(packet: &Packet, schema: Option<&Schema>, opts: AutoCompressOpts) -> Vec<u8> {
+    let mut payload = compress_varuint(packet.files.len() as u128);
+    payload.push(packet.names.is_some());
+    payload.push(packet.answers.is_some());
+    if let Some(names) = &packet.names {
+        assert_eq!(names.len(), packet.files.len());
+        let raw: Vec<(bool, &[u8])> = names.iter().map(|name| (true, name.as_bytes())).collect();
+        push_group(&mut payload, crate::compress(names, opts), &raw);
+    }
+    let raw: Vec<(bool, &[u8])> = packet.files.iter().map(raw_file).collect();
+    let data = match schema {
+        Some(schema) => crate::compress_with_schema(&packet.files, schema, opts),
+        None => crate::compress(&packet.files, opts),
+    };
+    push_group(&mut payload, data, &raw);
+    let (inputs, answers): (Vec<&FileContents>, Vec<&FileContents>) = match &packet.answers {
+        Some(answers) => {
+            assert_eq!(answers.len(), packet.files.len());
+            for answer in answers {
+                payload.push(answer.is_some());
+            }
+            packet
+                .files
+                .iter()
+                .zip(answers)
+                .filter_map(|(input, answer)| Some((input, answer.as_ref()?)))
+                .unzip()
+        }
+        None => (Vec::new(), Vec::new()),
+    };
+    if !answers.is_empty() {
+        let raw: Vec<(bool, &[u8])> = answers.iter().map(|answer| raw_file(answer)).collect();
+        push_group(
+            &mut payload,
+            answers_compressed(&inputs, &answers, opts),
+            &raw,
+        );
+    }
+
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(MAGIC);
+    bytes.push(VERSION);
+    bytes.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(&payload.to_bytes());
+    for file in packet.files.iter().chain(answers) {
+        bytes.extend_from_slice(&crc32(file.as_bytes()).to_le_bytes());
+    }
+    bytes
+}
+
+fn into_file(value: Value) -> Result<FileContents, DecodeError> {
+    match value {
+        Value::Str(s) => Ok(FileContents::Text(s)),
+        Value::Bytes(bytes) => Ok(FileContents::Binary(bytes)),
+        _ => Err(DecodeError::InvalidData("expected file contents")),
+    }
+}
+
+// Returns the payload and the offset of the checksums
+fn read_payload(bytes: &[u8]) -> Result<(Bits, usize), DecodeError> {
+    if bytes.len() < MAGIC.len() || &bytes[..MAGIC.len()] != MAGIC {
+        return Err(DecodeError::InvalidMagic);
+    }
+    if bytes.len() < HEADER_LENGTH {
+        return Err(DecodeError::UnexpectedEnd);
+    }
+    let version = bytes[MAGIC.len()];
+    if version != VERSION {
+        return Err(DecodeError::UnsupportedVersion(version));
+    }
+
+    let payload_bits =
+        u64::from_le_bytes(bytes[MAGIC.len() + 1..HEADER_LENGTH].try_into().unwrap());
+    let payload_bytes = payload_bits.div_ceil(8);
+    if payload_bytes > (bytes.len() - HEADER_LENGTH) as u64 {
+        return Err(DecodeError::UnexpectedEnd);
+    }
+    let payload_end = HEADER_LENGTH + payload_bytes as usize;
+    let payload =
+        Bits::from_bytes(&bytes[HEADER_LENGTH..payload_end]).slice(0..payload_bits as usize);
+    Ok((payload, payload_end))
+}
+
+/// Restores files from a packet, verifying its format version and checksums.
+pub fn unpack(bytes: &[u8]) -> Result<Packet, DecodeError> {
+    let (payload, payload_end) = read_payload(bytes)?;
+    let mut reader = BitReader::new(&payload);
+    let n_files = usize::try_from(decompress_varuint(&mut reader)?)
+        .map_err(|_| DecodeError::InvalidData("too many files"))?;
+    let has_names = reader.read()?;
+    let has_answers = reader.read()?;
+    let names = if has_names {
+        Some(
+            decode_group(&mut reader, n_files)?
+                .into_iter()
+                .map(Value::into_string)
+                .collect::<Result<_, _>>()?,
+        )
+    } else {
+        None
+    };
+    let files = decode_group(&mut reader, n_files)?
+        .into_iter()
+        .map(into_file)
+        .collect::<Result<Vec<_>, _>>()?;
+    let answers = if has_answers {
+        let mut present = Vec::with_capacity(n_files);
+        for _ in 0..n_files {
+            present.push(reader.read()?);
+        }
+        let inputs: Vec<Value> = files
+            .iter()
+            .zip(&present)
+            .filter(|(_, present)| **present)
+            .map(|(input, _)| input.to_value())
+            .collect();
+        let mut answers = if inputs.is_empty() {
+            Vec::new()
+        } else {
+            decode_answers(&mut reader, inputs)?
+        }
+        .into_iter();
+        Some(
+            present
+                .into_iter()
+                .map(|present| {
+                    present
+                        .then(|| into_file(answers.next().unwrap()))
+                        .transpose()
+                })
+                .collect::<Result<Vec<_>, _>>()?,
+        )
+    } else {
+        None
+    };
+    if reader.position() != payload.len() {
+        return Err(DecodeError::TrailingData);
+    }
+
+    let mut checksums = bytes[payload_end..].chunks(4);
+    let answer_files = answers.iter().flatten().flatten();
+    for (i, file) in files.iter().chain(answer_files).enumerate() {
+        let checksum = checksums.next().ok_or(DecodeError::UnexpectedEnd)?;
+        if checksum.len() < 4 {
+            return Err(DecodeError::UnexpectedEnd);
+        }
+        if u32::from_le_bytes(checksum.try_into().unwrap()) != crc32(file.as_bytes()) {
+            return Err(DecodeError::ChecksumMismatch { file: i });
+        }
+    }
+    if checksums.next().is_some() {
+        return Err(DecodeError::TrailingData);
+    }
+
+    Ok(Packet {
+        names,
+        files,
+        answers,
+    })
+}
+
+// Reads the engine of the answers and an answer to each of the inputs
+fn decode_answers(reader: &mut BitReader, inputs: Vec<Value>) -> Result<Vec<Value>, DecodeError> {
+    let engine = Engine::read(reader)?;
+    let mut decoder = Decoder::new(&engine)?;
+    let n_answers = inputs.len();
+    decoder.set_inputs(inputs);
+    let mut answers = Vec::new();
+    for _ in 0..n_answers {
+        answers.push(decoder.decode(reader)?);
+    }
+    Ok(answers)
+}
+
+// Engines of the file names, if stored, and of the files, but not of the answers
+pub(crate) fn packet_engines(bytes: &[u8]) -> Result<Vec<Engine>, DecodeError> {
+    let (payload, _) = read_payload(bytes)?;
+    let mut reader = BitReader::new(&payload);
+    let n_files = usize::try_from(decompress_varuint(&mut reader)?)
+        .map_err(|_| DecodeError::InvalidData("too many files"))?;
+    let n_groups = if reader.read()? { 2 } else { 1 };
+    reader.read()?;
+    let mut engines = Vec::new();
+    for _ in 0..n_groups {
+        let engine = Engine::read(&mut reader)?;
+        let mut decoder = Decoder::new(&engine)?;
+        reader.reserve_items(n_files)?;
+        for _ in 0..n_files {
+            decoder.decode(&mut reader)?;
+        }
+        engines.push(engine);
+    }
+    Ok(engines)
+}
+
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for byte in data {
+        crc ^= *byte as u32;
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb88320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
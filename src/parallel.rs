@@ -0,0 +1,77 @@
+use std::panic::resume_unwind;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::sync::OnceLock;
+use std::thread;
+
+// Independent branches of the search run on scoped threads. The number of extra threads alive at
+// once is bounded by the available parallelism; when the bound is reached, branches run on the
+// calling thread instead.
+static SPAWNED: AtomicUsize = AtomicUsize::new(0);
+
+// The search recurses deeply, so worker threads get as much stack as the main thread
+const STACK_SIZE: usize = 8 << 20;
+
+fn max_spawned() -> usize {
+    static MAX: OnceLock<usize> = OnceLock::new();
+    *MAX.get_or_init(|| thread::available_parallelism().map_or(1, |n| n.get()) - 1)
+}
+
+fn try_reserve() -> bool {
+    SPAWNED
+        .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |n| {
+            (n < max_spawned()).then_some(n + 1)
+        })
+        .is_ok()
+}
+
+struct Reservation;
+
+impl Drop for Reservation {
+    fn drop(&mut self) {
+        SPAWNED.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+// Runs two closures, possibly in parallel
+pub fn join<A, B, RA, RB>(a: A, b: B) -> (RA, RB)
+where
+    A: FnOnce() -> RA + Send,
+    B: FnOnce() -> RB + Send,
+    RA: Send,
+    RB: Send,
+{
+    if !try_reserve() {
+        return (a(), b());
+    }
+    let reservation = Reservation;
+    thread::scope(|scope| {
+        let handle = thread::Builder::new()
+            .stack_size(STACK_SIZE)
+            .spawn_scoped(scope, move || {
+                let _reservation = reservation;
+                b()
+            })
+            .expect("Failed to spawn a worker thread");
+        let ra = a();
+        (ra, handle.join().unwrap_or_else(|err| resume_unwind(err)))
+    })
+}
+
+// Maps items, possibly in parallel. The results are in the same order as the items
+pub fn map<T, R, F>(items: &[T], f: F) -> Vec<R>
+where
+    T: Sync,
+    R: Send,
+    F: Fn(&T) -> R + Sync,
+{
+    fn go<T: Sync, R: Send, F: Fn(&T) -> R + Sync>(items: &[T], f: &F) -> Vec<R> {
+        if items.len() <= 1 {
+            return items.iter().map(f).collect();
+        }
+        let (left, right) = items.split_at(items.len() / 2);
+        let (mut left, right) = join(|| go(left, f), || go(right, f));
+        left.extend(right);
+        left
+    }
+    go(items, &f)
+}
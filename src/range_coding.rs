@@ -0,0 +1,299 @@
+use crate::autocompress::{autocompress_one, AutoCompressOpts};
+use crate::bits::{BitReader, Bits};
+use crate::compress::{Compress, Engine, MultiCompressedData};
+use crate::decompress::DecodeError;
+use std::collections::HashMap;
+
+// Range coder in the style of LZMA. Frequencies of symbols must sum up to at most MAX_TOTAL.
+pub const MAX_TOTAL: u32 = 1 << 16;
+const TOP: u32 = 1 << 24;
+
+pub struct RangeEncoder {
+    low: u64,
+    range: u32,
+    cache: u8,
+    cache_size: u64,
+    bytes: Vec<u8>,
+}
+
+impl RangeEncoder {
+    pub fn new() -> Self {
+        Self {
+            low: 0,
+            range: u32::MAX,
+            cache: 0,
+            cache_size: 1,
+            bytes: Vec::new(),
+        }
+    }
+
+    pub fn encode(&mut self, start: u32, size: u32, total: u32) {
+        let r = self.range / total;
+        self.low += r as u64 * start as u64;
+        self.range = r * size;
+        while self.range < TOP {
+            self.range <<= 8;
+            self.shift_low();
+        }
+    }
+
+    fn shift_low(&mut self) {
+        if self.low < 0xff000000 || self.low >= 1 << 32 {
+            let carry = (self.low >> 32) as u8;
+            let mut byte = self.cache;
+            loop {
+                self.bytes.push(byte.wrapping_add(carry));
+                byte = 0xff;
+                self.cache_size -= 1;
+                if self.cache_size == 0 {
+                    break;
+                }
+            }
+            self.cache = (self.low >> 24) as u8;
+        }
+        self.cache_size += 1;
+        self.low = (self.low & 0x00ffffff) << 8;
+    }
+
+    pub fn finish(mut self) -> Bits {
+        for _ in 0..5 {
+            self.shift_low();
+        }
+        // The first byte is always zero, so it's not stored
+        let mut bits = Bits::new();
+        for byte in &self.bytes[1..] {
+            bits.push_int(*byte as u128, 8);
+        }
+        bits
+    }
+}
+
+pub struct RangeDecoder<'a, 'b> {
+    reader: &'a mut BitReader<'b>,
+    code: u32,
+    range: u32,
+    r: u32,
+}
+
+impl<'a, 'b> RangeDecoder<'a, 'b> {
+    pub fn new(reader: &'a mut BitReader<'b>) -> Result<Self, DecodeError> {
+        let code = reader.read_int(32)? as u32;
+        Ok(Self {
+            reader,
+            code: code.swap_bytes(),
+            range: u32::MAX,
+            r: 0,
+        })
+    }
+
+    // Returns a value in [0, total) which lies within the frequency range of the encoded symbol
+    pub fn decode_freq(&mut self, total: u32) -> u32 {
+        self.r = self.range / total;
+        (self.code / self.r).min(total - 1)
+    }
+
+    // Must be called after decode_freq with the frequency range of the decoded symbol
+    pub fn decode_update(&mut self, start: u32, size: u32) -> Result<(), DecodeError> {
+        self.code = self
+            .code
+            .checked_sub(start * self.r)
+            .ok_or(DecodeError::InvalidData("invalid range-coded data"))?;
+        self.range = self.r * size;
+        while self.range < TOP {
+            self.range <<= 8;
+            self.code = (self.code << 8) | self.reader.read_int(8)? as u32;
+        }
+        Ok(())
+    }
+}
+
+fn cumulative(freqs: &[u32]) -> Vec<u32> {
+    let mut cum = Vec::with_capacity(freqs.len() + 1);
+    cum.push(0);
+    for freq in freqs {
+        cum.push(cum.last().unwrap() + freq);
+    }
+    cum
+}
+
+pub fn encode_range_coded(symbols: &[usize], freqs: &[u32]) -> Bits {
+    let cum = cumulative(freqs);
+    let total = *cum.last().unwrap();
+    let mut encoder = RangeEncoder::new();
+    for symbol in symbols {
+        encoder.encode(cum[*symbol], freqs[*symbol], total);
+    }
+    encoder.finish()
+}
+
+// Every object takes at least `min_bits` bits of coded data, so a length that needs more bits
+// than are left is invalid. The length comes from the packet, so it's not trusted for allocation
+// either way
+pub(crate) fn check_length(
+    reader: &BitReader,
+    length: usize,
+    min_bits: f64,
+) -> Result<(), DecodeError> {
+    if length as f64 * min_bits > reader.remaining() as f64 + 8.0 {
+        return Err(DecodeError::InvalidData(
+            "range-coded length doesn't fit the data",
+        ));
+    }
+    Ok(())
+}
+
+pub fn decode_range_coded(
+    reader: &mut BitReader,
+    length: usize,
+    freqs: &[u32],
+) -> Result<Vec<usize>, DecodeError> {
+    let cum = cumulative(freqs);
+    let total = *cum.last().unwrap();
+    if total == 0 || total > MAX_TOTAL || freqs.contains(&0) {
+        return Err(DecodeError::InvalidData("invalid symbol frequencies"));
+    }
+    // A single symbol takes no bits, so nothing would bound the length. The encoder uses
+    // constants instead
+    if freqs.len() < 2 {
+        return Err(DecodeError::InvalidData(
+            "range-coded alphabet has a single symbol",
+        ));
+    }
+    let max_freq = *freqs.iter().max().unwrap();
+    check_length(reader, length, (total as f64 / max_freq as f64).log2())?;
+    let mut decoder = RangeDecoder::new(reader)?;
+    let mut symbols = Vec::new();
+    for _ in 0..length {
+        let value = decoder.decode_freq(total);
+        let symbol = cum.partition_point(|start| *start <= value) - 1;
+        decoder.decode_update(cum[symbol], freqs[symbol])?;
+        symbols.push(symbol);
+    }
+    Ok(symbols)
+}
+
+// Scales counts down so that they sum up to at most MAX_TOTAL, keeping every count positive
+fn quantize_counts(counts: &[usize]) -> Vec<u32> {
+    let total: usize = counts.iter().sum();
+    if total <= MAX_TOTAL as usize {
+        return counts.iter().map(|count| *count as u32).collect();
+    }
+    let mut freqs: Vec<u32> = counts
+        .iter()
+        .map(|count| ((*count as u128 * MAX_TOTAL as u128 / total as u128) as u32).max(1))
+        .collect();
+    let mut sum: u32 = freqs.iter().sum();
+    while sum > MAX_TOTAL {
+        let largest = (0..freqs.len()).max_by_key(|i| freqs[*i]).unwrap();
+        let excess = (sum - MAX_TOTAL).min(freqs[largest] / 2);
+        freqs[largest] -= excess;
+        sum -= excess;
+    }
+    freqs
+}
+
+// Like Huffman coding, but spends a fractional number of bits per object. The coded data of all
+// objects is stored in the engine, in order.
+pub fn range_coded<T: Compress>(
+    objs: &[&T],
+    opts: AutoCompressOpts,
+) -> Option<MultiCompressedData> {
+    let mut counts: HashMap<&T, usize> = HashMap::new();
+    let mut alphabet: Vec<&T> = Vec::new();
+    for obj in objs {
+        let count = counts.entry(obj).or_default();
+        if *count == 0 {
+            alphabet.push(obj);
+        }
+        *count += 1;
+    }
+    // A single symbol is a constant, which the decoder doesn't accept as range-coded
+    if alphabet.len() < 2 || alphabet.len() > MAX_TOTAL as usize {
+        return None;
+    }
+
+    // Non-increasing counts are usually cheaper to store
+    alphabet.sort_by_key(|obj| std::cmp::Reverse(counts[obj]));
+    let index_of: HashMap<&T, usize> = alphabet
+        .iter()
+        .enumerate()
+        .map(|(i, obj)| (*obj, i))
+        .collect();
+
+    let counts: Vec<usize> = alphabet.iter().map(|obj| counts[obj]).collect();
+    let freqs = quantize_counts(&counts);
+    let freqs_usize: Vec<usize> = freqs.iter().map(|freq| *freq as usize).collect();
+    let freqs_refs: Vec<&usize> = freqs_usize.iter().collect();
+
+    let symbols: Vec<usize> = objs.iter().map(|obj| index_of[obj]).collect();
+
+    let alphabet_compressed = autocompress_one(&alphabet, opts);
+    let freqs_compressed = autocompress_one(&freqs_refs, opts);
+
+    Some(MultiCompressedData {
+        engine: Engine::RangeCoded {
+            length: objs.len(),
+            alphabet_engine: Box::new(alphabet_compressed.engine),
+            alphabet_data: alphabet_compressed.binary_data,
+            freqs_engine: Box::new(freqs_compressed.engine),
+            freqs_data: freqs_compressed.binary_data,
+            data: encode_range_coded(&symbols, &freqs),
+        },
+        binary_data: vec![Bits::new(); objs.len()],
+    })
+}
+
+// Uniformly distributed integers in [0, size) are split into a top digit and 16-bit low digits,
+// each of which is range-coded
+fn int_digits(size: u128) -> (usize, u32) {
+    let mut n_low_digits = 0;
+    while (size - 1) >> (16 * n_low_digits) >= MAX_TOTAL as u128 {
+        n_low_digits += 1;
+    }
+    (n_low_digits, ((size - 1) >> (16 * n_low_digits)) as u32 + 1)
+}
+
+pub fn encode_range_coded_ints(nums: &[u128], size: u128) -> Bits {
+    let (n_low_digits, top_total) = int_digits(size);
+    let mut encoder = RangeEncoder::new();
+    for num in nums {
+        encoder.encode((num >> (16 * n_low_digits)) as u32, 1, top_total);
+        for i in (0..n_low_digits).rev() {
+            encoder.encode(((num >> (16 * i)) & 0xffff) as u32, 1, MAX_TOTAL);
+        }
+    }
+    encoder.finish()
+}
+
+pub fn decode_range_coded_ints(
+    reader: &mut BitReader,
+    length: usize,
+    size: u128,
+) -> Result<Vec<u128>, DecodeError> {
+    // As with symbols, a single integer takes no bits
+    if size < 2 {
+        return Err(DecodeError::InvalidData(
+            "range-coded integer range is too small",
+        ));
+    }
+    check_length(reader, length, (size as f64).log2())?;
+    let (n_low_digits, top_total) = int_digits(size);
+    let mut decoder = RangeDecoder::new(reader)?;
+    let mut nums = Vec::new();
+    for _ in 0..length {
+        let mut num = decoder.decode_freq(top_total) as u128;
+        decoder.decode_update(num as u32, 1)?;
+        for _ in 0..n_low_digits {
+            let digit = decoder.decode_freq(MAX_TOTAL);
+            decoder.decode_update(digit, 1)?;
+            num = (num << 16) | digit as u128;
+        }
+        if num >= size {
+            return Err(DecodeError::InvalidData(
+                "range-coded integer is out of range",
+            ));
+        }
+        nums.push(num);
+    }
+    Ok(nums)
+}
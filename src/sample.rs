@@ -0,0 +1,407 @@
+use crate::bits::{BitReader, Bits};
+use crate::compress::MultiCompressedData;
+use crate::decompress::{DecodeError, Decoder, Value};
+use std::collections::BTreeSet;
+
+// SplitMix64, good enough for test data and stable across platforms and versions, so that a seed
+// always produces the same files
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9e3779b97f4a7c15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xbf58476d1ce4e5b9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94d049bb133111eb);
+        z ^ (z >> 31)
+    }
+
+    fn next_u128(&mut self) -> u128 {
+        ((self.next() as u128) << 64) | self.next() as u128
+    }
+
+    fn bit(&mut self) -> bool {
+        self.next() >> 63 == 1
+    }
+
+    fn bits(&mut self, bit_length: usize) -> u128 {
+        if bit_length == 0 {
+            0
+        } else {
+            self.next_u128() >> (128 - bit_length)
+        }
+    }
+
+    // Uniform in 0..n, up to a negligible bias. n = 0 stands for 2^128, the size of the widest
+    // range of i128
+    fn below(&mut self, n: u128) -> u128 {
+        match n {
+            0 => self.next_u128(),
+            _ => self.next_u128() % n,
+        }
+    }
+}
+
+// Random draws that can miss, e.g. a fixed-length index into an alphabet whose size is not a power
+// of two, are repeated at most this many times
+const MAX_ATTEMPTS: usize = 100;
+
+// Variable-length integers are drawn as if from random bits, which makes small numbers the most
+// likely. Numbers of more than this many bits are drawn again, so that lengths stay reasonable
+const MAX_VARINT_BITS: usize = 20;
+
+fn sample_varuint(rng: &mut Rng) -> u128 {
+    match (rng.bit(), rng.bit()) {
+        (false, false) => 0,
+        (true, false) => 1,
+        (false, true) => 2,
+        (true, true) => {
+            let n_bits = (sample_varuint(rng) + 2).min(128) as usize;
+            (1 << (n_bits - 1)) | rng.bits(n_bits - 1)
+        }
+    }
+}
+
+fn sample_varint(rng: &mut Rng) -> i128 {
+    loop {
+        let negative = rng.bit();
+        let num = sample_varuint(rng);
+        if num < 1 << MAX_VARINT_BITS {
+            let num = num as i128;
+            break if negative { -num - 1 } else { num };
+        }
+    }
+}
+
+// Uniform in min..=max
+fn sample_range(min: i128, max: i128, rng: &mut Rng) -> i128 {
+    let range = (max.wrapping_sub(min) as u128).wrapping_add(1);
+    min.wrapping_add(rng.below(range) as i128)
+}
+
+// Draws an index below `length`, repeating draws that are out of range
+fn sample_index(decoder: &Decoder, length: usize, rng: &mut Rng) -> Result<usize, DecodeError> {
+    for _ in 0..MAX_ATTEMPTS {
+        let i = sample_value(decoder, rng)?.into_usize()?;
+        if i < length {
+            return Ok(i);
+        }
+    }
+    Err(DecodeError::InvalidData("index is out of range"))
+}
+
+// Sorted numbers in min..=max, distinct if `unique`
+fn sample_set(length: usize, min: i128, max: i128, unique: bool, rng: &mut Rng) -> Vec<i128> {
+    if !unique {
+        let mut nums: Vec<i128> = (0..length).map(|_| sample_range(min, max, rng)).collect();
+        nums.sort();
+        return nums;
+    }
+    let range = (max.wrapping_sub(min) as u128).wrapping_add(1);
+    // Floyd's algorithm picks `length` distinct offsets with equal probability
+    let mut offsets = BTreeSet::new();
+    for j in range - length as u128..range {
+        let offset = rng.below(j + 1);
+        if !offsets.insert(offset) {
+            offsets.insert(j);
+        }
+    }
+    offsets
+        .into_iter()
+        .map(|offset| min.wrapping_add(offset as i128))
+        .collect()
+}
+
+// Mirrors Decoder::decode, drawing random values instead of reading bits. Huffman codes are
+// followed bit by bit, so each symbol is as likely as in the compressed data. Integers are drawn
+// from the range of the values their node has decoded, as an encoding only bounds them loosely,
+// e.g. a fixed width of 17 bits for numbers up to 100000. Objects that were decoded in advance,
+// e.g. the items of a stateful list, are replayed verbatim: each draw picks one of them
+fn sample_value(decoder: &Decoder, rng: &mut Rng) -> Result<Value, DecodeError> {
+    Ok(match decoder {
+        Decoder::VarInt => Value::Int(sample_varint(rng)),
+        Decoder::FixedInt { bias, length } => {
+            Value::Int(bias.wrapping_add(rng.bits(*length) as i128))
+        }
+        Decoder::Huffman {
+            alphabet,
+            codes,
+            max_length,
+        } => {
+            let mut code = (0, 0);
+            let mut attempts = 0;
+            loop {
+                if let Some(i) = codes.get(&code) {
+                    break alphabet[*i].clone();
+                }
+                if code.0 >= *max_length {
+                    // The tree is incomplete, e.g. if it has a single symbol
+                    attempts += 1;
+                    if attempts == MAX_ATTEMPTS {
+                        return Err(DecodeError::InvalidData("unknown Huffman code"));
+                    }
+                    code = (0, 0);
+                    continue;
+                }
+                code = (code.0 + 1, code.1 * 2 + rng.bit() as u128);
+            }
+        }
+        Decoder::String { chars } => Value::Str(
+            sample_value(chars, rng)?
+                .into_vec()?
+                .into_iter()
+                .map(Value::into_char)
+                .collect::<Result<_, _>>()?,
+        ),
+        Decoder::StringConcat { words, separator } => Value::Str(
+            sample_value(words, rng)?
+                .into_vec()?
+                .into_iter()
+                .map(Value::into_string)
+                .collect::<Result<Vec<_>, _>>()?
+                .join(&separator.to_string()),
+        ),
+        Decoder::IntSet {
+            length,
+            min,
+            max,
+            unique,
+        } => {
+            let length = sample_value(length, rng)?.into_usize()?;
+            let min = sample_value(min, rng)?.into_int()?;
+            let max = sample_value(max, rng)?.into_int()?;
+            let fits = min <= max
+                && (!*unique
+                    || length >= 2
+                        && max
+                            .checked_sub(min)
+                            .is_some_and(|d| d >= length as i128 - 1));
+            if !fits {
+                return Err(DecodeError::InvalidData("invalid integer set bounds"));
+            }
+            let nums = if *unique {
+                let mut nums = vec![min];
+                nums.extend(sample_set(length - 2, min + 1, max - 1, true, rng));
+                nums.push(max);
+                nums
+            } else {
+                sample_set(length, min, max, false, rng)
+            };
+            Value::Vec(nums.into_iter().map(Value::Int).collect())
+        }
+        Decoder::Queue { items } => {
+            if items.is_empty() {
+                return Err(DecodeError::InvalidData("no objects to sample from"));
+            }
+            items[rng.below(items.len() as u128) as usize].clone()
+        }
+        Decoder::Vec { length, item } => {
+            if let Some((kinds, categories)) = sample_layout(length, item, rng) {
+                let items = kinds
+                    .into_iter()
+                    .map(|i| {
+                        let category = categories
+                            .get(i)
+                            .ok_or(DecodeError::InvalidData("category is out of range"))?;
+                        sample_value(category, rng)
+                    })
+                    .collect::<Result<_, _>>()?;
+                return Ok(Value::Vec(items));
+            }
+            let length = sample_value(length, rng)?.into_usize()?;
+            let mut items = Vec::new();
+            for _ in 0..length {
+                items.push(sample_value(item, rng)?);
+            }
+            Value::Vec(items)
+        }
+        Decoder::VecRLE { length, item } => {
+            // Run lengths and values are drawn independently, so their counts may differ
+            let run_lengths = sample_value(length, rng)?.into_vec()?;
+            let run_values = sample_value(item, rng)?.into_vec()?;
+            let mut items = Vec::new();
+            for (run_length, value) in run_lengths.into_iter().zip(run_values) {
+                for _ in 0..run_length.into_usize()? {
+                    items.push(value.clone());
+                }
+            }
+            Value::Vec(items)
+        }
+        Decoder::CategorySplit {
+            categories,
+            category,
+        } => {
+            let i = sample_index(category, categories.len(), rng)?;
+            sample_value(&categories[i], rng)?
+        }
+        Decoder::Constant { value } => value.clone(),
+        Decoder::Alphabet { alphabet, index } => {
+            alphabet[sample_index(index, alphabet.len(), rng)?].clone()
+        }
+        Decoder::StringifiedInt { inner } => {
+            Value::Str(sample_value(inner, rng)?.into_int()?.to_string())
+        }
+        Decoder::StringifiedDecimal { inner, precision } => {
+            let mut s = sample_value(inner, rng)?.into_int()?.to_string();
+            let precision = sample_value(precision, rng)?.into_usize()?;
+            if precision > s.len() {
+                return Err(DecodeError::InvalidData("precision is out of range"));
+            }
+            s.insert(s.len() - precision, '.');
+            Value::Str(s)
+        }
+        Decoder::Bytes { inner } => Value::Bytes(
+            sample_value(inner, rng)?
+                .into_vec()?
+                .into_iter()
+                .map(|byte| {
+                    u8::try_from(byte.into_int()?)
+                        .map_err(|_| DecodeError::InvalidData("expected a byte"))
+                })
+                .collect::<Result<_, _>>()?,
+        ),
+        Decoder::Schema {
+            schema,
+            newline,
+            fields,
+        } => {
+            let newline = sample_value(newline, rng)?.into_usize()? != 0;
+            // Nothing is read, but counts are still limited
+            let bits = Bits::new();
+            let mut text = schema.generate(&mut BitReader::new(&bits), |slot, _| {
+                sample_value(&fields[slot], rng)
+            })?;
+            if newline {
+                text.push('\n');
+            }
+            Value::Str(text)
+        }
+        // The number of lines is only known from an input
+        Decoder::AnswerLines { .. } => {
+            return Err(DecodeError::InvalidData("answers need their inputs"))
+        }
+        Decoder::Stored => {
+            return Err(DecodeError::InvalidData(
+                "stored objects have no structure to follow",
+            ))
+        }
+        Decoder::Profiled { inner, values, .. } => match &**inner {
+            Decoder::FixedInt { .. } | Decoder::VarInt if !values.is_empty() => {
+                let nums = values
+                    .iter()
+                    .map(|value| value.clone().into_int())
+                    .collect::<Result<Vec<_>, _>>()?;
+                let min = *nums.iter().min().unwrap();
+                let max = *nums.iter().max().unwrap();
+                Value::Int(sample_range(min, max, rng))
+            }
+            // Whether text ends with a line break is taken from one of the compressed texts, as the
+            // lines may be drawn in any order
+            Decoder::StringConcat {
+                separator: '\n', ..
+            } if !values.is_empty() => {
+                let mut text = sample_value(inner, rng)?.into_string()?;
+                let template = values[rng.below(values.len() as u128) as usize].clone();
+                let ends_with_newline = template.into_string()?.ends_with('\n');
+                if ends_with_newline && !text.ends_with('\n') {
+                    text.push('\n');
+                } else if !ends_with_newline && text.ends_with('\n') {
+                    text.pop();
+                }
+                Value::Str(text)
+            }
+            // The queue is empty once the data is decoded, but its items are recorded
+            Decoder::Queue { .. } => {
+                if values.is_empty() {
+                    return Err(DecodeError::InvalidData("no objects to sample from"));
+                }
+                values[rng.below(values.len() as u128) as usize].clone()
+            }
+            _ => sample_value(inner, rng)?,
+        },
+    })
+}
+
+// If the items of a list are split into categories, e.g. lines of different kinds, the categories
+// of the items of one of the compressed lists, and the decoders of the categories. Drawing the
+// category of each item independently would shuffle the layout, e.g. move the empty string after
+// the final line break of a file to the middle
+fn sample_layout<'a>(
+    length: &Decoder,
+    item: &'a Decoder,
+    rng: &mut Rng,
+) -> Option<(Vec<usize>, &'a [Decoder])> {
+    let (
+        Decoder::Profiled {
+            values: lengths, ..
+        },
+        Decoder::Profiled { inner, .. },
+    ) = (length, item)
+    else {
+        return None;
+    };
+    let Decoder::CategorySplit {
+        categories,
+        category,
+    } = &**inner
+    else {
+        return None;
+    };
+    let Decoder::Profiled {
+        values: category_by_item,
+        ..
+    } = &**category
+    else {
+        return None;
+    };
+    if lengths.is_empty() {
+        return None;
+    }
+
+    let lengths: Vec<usize> = lengths
+        .iter()
+        .map(|length| length.clone().into_usize().ok())
+        .collect::<Option<_>>()?;
+    let i = rng.below(lengths.len() as u128) as usize;
+    let start: usize = lengths[..i].iter().sum();
+    let kinds = category_by_item
+        .get(start..start + lengths[i])?
+        .iter()
+        .map(|category| category.clone().into_usize().ok())
+        .collect::<Option<_>>()?;
+    Some((kinds, categories))
+}
+
+/// Generates `n` random objects with the structure of compressed objects, e.g. new tests that look
+/// like the ones in a test pack. Values follow the distributions the engine has recorded, integers
+/// stay within the range of the compressed ones, and the same seed always gives the same objects.
+///
+/// Lists whose items are split into categories, such as the lines of files of different kinds,
+/// follow the categories of one of the compressed lists, so files keep their layout, e.g. the
+/// number of tokens on each line and the final line break. Otherwise values are drawn
+/// independently at each node of the engine tree, so relations between values, such as a count and
+/// the length of the list that follows it, are only kept if the engine stores them, as the engines
+/// of [`compress_with_schema`](crate::compress_with_schema) do. Parts
+/// that are decoded in advance rather than per object, such as the items of stateful lists and
+/// range-coded data, are not generated but replayed verbatim from the compressed objects.
+pub fn sample(data: &MultiCompressedData, seed: u64, n: usize) -> Result<Vec<Value>, DecodeError> {
+    // The compressed objects are decoded to find the values each node produces
+    let mut decoder = Decoder::new_profiled(&data.engine)?;
+    for chunk in &data.binary_data {
+        decoder.decode(&mut BitReader::new(chunk))?;
+    }
+    let mut rng = Rng(seed);
+    let mut objs = Vec::with_capacity(n);
+    for _ in 0..n {
+        // A draw can fail as a whole, e.g. if a string gets a code that is not a character
+        let mut result = sample_value(&decoder, &mut rng);
+        for _ in 1..MAX_ATTEMPTS {
+            if result.is_ok() {
+                break;
+            }
+            result = sample_value(&decoder, &mut rng);
+        }
+        objs.push(result?);
+    }
+    Ok(objs)
+}
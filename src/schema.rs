@@ -0,0 +1,586 @@
+use crate::autocompress::{autocompress, AutoCompressOpts};
+use crate::bits::{BitReader, Bits};
+use crate::compress::{Engine, MultiCompressedData};
+use crate::decompress::{DecodeError, Decoder, Value};
+use crate::parallel::map;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
+
+// Schema language:
+// - lines are separated by '/', fields of a line by spaces,
+// - `int` is an integer, `str` is a token of any other kind,
+// - any other identifier is a named integer, which can be used as a count later,
+// - `k ints` and `k strs` are k integers or tokens on the same line,
+// - `k x <line>` and `k x (<lines>)` repeat lines k times,
+// - `k pairs` and `k triples` are shorthands for `k x int int` and `k x int int int`,
+// - integers can be followed by a range, e.g. `n in [1, 2e5]`.
+// Counts are either numbers or names of integers read before.
+//
+// Each field of the schema is a slot, and the values of a slot in all files are compressed as a
+// single group, so that integer and string coders are still chosen automatically.
+
+/// Input format of a file, used to compress files that follow it without guessing their structure.
+///
+/// For example, `n m / n ints in [1, 1e9] / m pairs` describes a line with two integers `n` and
+/// `m`, a line with `n` integers, and `m` lines with two integers each.
+#[derive(Clone, Debug)]
+pub struct Schema {
+    // Normalized source, which is stored in the header
+    text: String,
+    items: Vec<Item>,
+    n_slots: usize,
+}
+
+/// An error in the schema source.
+#[derive(Debug)]
+pub struct SchemaError(String);
+
+impl fmt::Display for SchemaError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "invalid schema: {}", self.0)
+    }
+}
+
+impl std::error::Error for SchemaError {}
+
+#[derive(Clone, Debug)]
+enum Count {
+    Const(usize),
+    Var(String),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Kind {
+    Int,
+    Str,
+}
+
+#[derive(Clone, Debug)]
+struct Field {
+    kind: Kind,
+    // Number of values on the line, if it's a list
+    repeat: Option<Count>,
+    name: Option<String>,
+    range: Option<(i128, i128)>,
+    slot: usize,
+}
+
+#[derive(Clone, Debug)]
+enum Item {
+    Line(Vec<Field>),
+    Repeat(Count, Vec<Item>),
+}
+
+const KEYWORDS: &[&str] = &["int", "ints", "str", "strs", "x", "pairs", "triples", "in"];
+
+fn tokenize(text: &str) -> Result<Vec<String>, SchemaError> {
+    let mut tokens = Vec::new();
+    let mut chars = text.chars().peekable();
+    while let Some(c) = chars.next() {
+        if c.is_whitespace() {
+            continue;
+        }
+        if "/()[],".contains(c) {
+            tokens.push(c.to_string());
+        } else if c.is_ascii_alphanumeric() || c == '_' || c == '-' {
+            let mut word = c.to_string();
+            while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
+                word.push(c);
+            }
+            tokens.push(word);
+        } else {
+            return Err(SchemaError(format!("unexpected character {c:?}")));
+        }
+    }
+    Ok(tokens)
+}
+
+// Integers can be written in scientific notation, e.g. 2e5
+fn parse_number(s: &str) -> Option<i128> {
+    let (mantissa, exponent) = s.split_once('e').unwrap_or((s, "0"));
+    let exponent: u32 = exponent.parse().ok()?;
+    mantissa
+        .parse::<i128>()
+        .ok()?
+        .checked_mul(10i128.checked_pow(exponent)?)
+}
+
+fn is_name(s: &str) -> bool {
+    s.starts_with(|c: char| c.is_ascii_alphabetic() || c == '_') && !KEYWORDS.contains(&s)
+}
+
+struct Parser {
+    tokens: Vec<String>,
+    pos: usize,
+    names: HashSet<String>,
+    n_slots: usize,
+}
+
+impl Parser {
+    fn peek(&self, offset: usize) -> Option<&str> {
+        self.tokens
+            .get(self.pos + offset)
+            .map(|token| token.as_str())
+    }
+
+    fn next(&mut self) -> Result<String, SchemaError> {
+        let token = self
+            .tokens
+            .get(self.pos)
+            .ok_or_else(|| SchemaError("unexpected end".to_string()))?
+            .clone();
+        self.pos += 1;
+        Ok(token)
+    }
+
+    fn expect(&mut self, expected: &str) -> Result<(), SchemaError> {
+        let token = self.next()?;
+        if token == expected {
+            Ok(())
+        } else {
+            Err(SchemaError(format!(
+                "expected {expected:?}, found {token:?}"
+            )))
+        }
+    }
+
+    fn is_count(&self, token: &str) -> bool {
+        parse_number(token).is_some_and(|n| n >= 0) || self.names.contains(token)
+    }
+
+    fn count(&mut self) -> Result<Count, SchemaError> {
+        let token = self.next()?;
+        if let Some(n) = parse_number(&token) {
+            usize::try_from(n)
+                .map(Count::Const)
+                .map_err(|_| SchemaError(format!("count {token} is out of range")))
+        } else if self.names.contains(&token) {
+            Ok(Count::Var(token))
+        } else {
+            Err(SchemaError(format!("{token:?} is not a known integer")))
+        }
+    }
+
+    fn range(&mut self) -> Result<Option<(i128, i128)>, SchemaError> {
+        if self.peek(0) != Some("in") {
+            return Ok(None);
+        }
+        self.next()?;
+        self.expect("[")?;
+        let min = self.number()?;
+        self.expect(",")?;
+        let max = self.number()?;
+        self.expect("]")?;
+        Ok(Some((min, max)))
+    }
+
+    fn number(&mut self) -> Result<i128, SchemaError> {
+        let token = self.next()?;
+        parse_number(&token).ok_or_else(|| SchemaError(format!("{token:?} is not a number")))
+    }
+
+    fn field(&mut self, kind: Kind, repeat: Option<Count>, name: Option<String>) -> Field {
+        let slot = self.n_slots;
+        self.n_slots += 1;
+        Field {
+            kind,
+            repeat,
+            name,
+            range: None,
+            slot,
+        }
+    }
+
+    fn block(&mut self) -> Result<Vec<Item>, SchemaError> {
+        let mut items = vec![self.item()?];
+        while self.peek(0) == Some("/") {
+            self.next()?;
+            items.push(self.item()?);
+        }
+        Ok(items)
+    }
+
+    fn item(&mut self) -> Result<Item, SchemaError> {
+        let Some(token) = self.peek(0) else {
+            return Err(SchemaError("unexpected end".to_string()));
+        };
+        if !self.is_count(token) {
+            return Ok(Item::Line(self.line()?));
+        }
+        match self.peek(1) {
+            Some("x") => {
+                let count = self.count()?;
+                self.next()?;
+                let items = if self.peek(0) == Some("(") {
+                    self.next()?;
+                    let items = self.block()?;
+                    self.expect(")")?;
+                    items
+                } else {
+                    vec![Item::Line(self.line()?)]
+                };
+                Ok(Item::Repeat(count, items))
+            }
+            Some(tuple @ ("pairs" | "triples")) => {
+                let size = if tuple == "pairs" { 2 } else { 3 };
+                let count = self.count()?;
+                self.next()?;
+                let range = self.range()?;
+                let fields = (0..size)
+                    .map(|_| Field {
+                        range,
+                        ..self.field(Kind::Int, None, None)
+                    })
+                    .collect();
+                Ok(Item::Repeat(count, vec![Item::Line(fields)]))
+            }
+            _ => Ok(Item::Line(self.line()?)),
+        }
+    }
+
+    fn line(&mut self) -> Result<Vec<Field>, SchemaError> {
+        let mut fields = Vec::new();
+        while let Some(token) = self.peek(0) {
+            if token == "/" || token == ")" {
+                break;
+            }
+            let field = if token == "int" {
+                self.next()?;
+                self.field(Kind::Int, None, None)
+            } else if token == "str" {
+                self.next()?;
+                self.field(Kind::Str, None, None)
+            } else if self.is_count(token) && matches!(self.peek(1), Some("ints" | "strs")) {
+                let count = self.count()?;
+                let kind = if self.next()? == "ints" {
+                    Kind::Int
+                } else {
+                    Kind::Str
+                };
+                self.field(kind, Some(count), None)
+            } else if matches!(self.peek(1), Some("ints" | "strs")) {
+                return Err(SchemaError(format!("{token:?} is not a known integer")));
+            } else if is_name(token) {
+                let name = self.next()?;
+                self.names.insert(name.clone());
+                self.field(Kind::Int, None, Some(name))
+            } else {
+                return Err(SchemaError(format!("unexpected {token:?}")));
+            };
+            let range = if field.kind == Kind::Int {
+                self.range()?
+            } else {
+                None
+            };
+            fields.push(Field { range, ..field });
+        }
+        if fields.is_empty() {
+            return Err(SchemaError("empty line".to_string()));
+        }
+        Ok(fields)
+    }
+}
+
+impl Schema {
+    /// Parses a schema, e.g. `n m / n ints in [1, 1e9] / m pairs`.
+    pub fn parse(text: &str) -> Result<Self, SchemaError> {
+        let tokens = tokenize(text)?;
+        let mut normalized = String::new();
+        for (i, token) in tokens.iter().enumerate() {
+            let glued = i == 0
+                || matches!(tokens[i - 1].as_str(), "(" | "[")
+                || matches!(token.as_str(), ")" | "]" | ",");
+            if !glued {
+                normalized.push(' ');
+            }
+            normalized.push_str(token);
+        }
+        let mut parser = Parser {
+            tokens,
+            pos: 0,
+            names: HashSet::new(),
+            n_slots: 0,
+        };
+        let items = parser.block()?;
+        if let Some(token) = parser.peek(0) {
+            return Err(SchemaError(format!("unexpected {token:?}")));
+        }
+        Ok(Self {
+            text: normalized,
+            items,
+            n_slots: parser.n_slots,
+        })
+    }
+
+    /// Number of fields in the schema, each of which is compressed separately.
+    pub fn n_slots(&self) -> usize {
+        self.n_slots
+    }
+
+    // The text is restricted to ASCII by the tokenizer, so characters take 7 bits each
+    pub(crate) fn push_to_bits(&self, bits: &mut Bits) {
+        bits.extend(&crate::varint::compress_varuint(self.text.len() as u128));
+        for byte in self.text.bytes() {
+            bits.push_int(byte as u128, 7);
+        }
+    }
+
+    pub(crate) fn read(reader: &mut BitReader) -> Result<Self, DecodeError> {
+        let length = usize::try_from(crate::varint::decompress_varuint(reader)?)
+            .map_err(|_| DecodeError::InvalidData("schema is too long"))?;
+        let mut text = String::new();
+        for _ in 0..length {
+            text.push(reader.read_int(7)? as u8 as char);
+        }
+        Self::parse(&text).map_err(|_| DecodeError::InvalidData("invalid schema"))
+    }
+
+    // Splits a file into the values of each slot, in the order in which they are read, or returns
+    // None if the file doesn't follow the schema
+    fn split<'a>(&self, text: &'a str) -> Option<FileValues<'a>> {
+        let (body, newline) = match text.strip_suffix('\n') {
+            Some(body) => (body, true),
+            None => (text, false),
+        };
+        let mut matcher = Matcher {
+            lines: body.split('\n').collect(),
+            line: 0,
+            vars: HashMap::new(),
+            values: Vec::new(),
+        };
+        matcher.items(&self.items)?;
+        (matcher.line == matcher.lines.len()).then_some(FileValues {
+            newline,
+            values: matcher.values,
+        })
+    }
+
+    // Restores a file, reading values from the decoders of slots
+    pub(crate) fn decode(
+        &self,
+        fields: &mut [Decoder],
+        reader: &mut BitReader,
+    ) -> Result<String, DecodeError> {
+        self.generate(reader, |slot, reader| fields[slot].decode(reader))
+    }
+
+    // Builds a file from the values of slots in the order in which they are read, without the
+    // final line break. Counts are checked against the reader, which the values may come from
+    pub(crate) fn generate(
+        &self,
+        reader: &mut BitReader,
+        next_value: impl FnMut(usize, &mut BitReader) -> Result<Value, DecodeError>,
+    ) -> Result<String, DecodeError> {
+        let mut generator = Generator {
+            reader,
+            next_value,
+            vars: HashMap::new(),
+            lines: Vec::new(),
+        };
+        generator.items(&self.items)?;
+        Ok(generator.lines.join("\n"))
+    }
+}
+
+impl fmt::Display for Schema {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.text)
+    }
+}
+
+struct FileValues<'a> {
+    newline: bool,
+    // Slot and token of each value
+    values: Vec<(usize, &'a str)>,
+}
+
+fn resolve(count: &Count, vars: &HashMap<String, i128>) -> Option<usize> {
+    match count {
+        Count::Const(n) => Some(*n),
+        Count::Var(name) => usize::try_from(*vars.get(name)?).ok(),
+    }
+}
+
+fn parse_int(token: &str, range: Option<(i128, i128)>) -> Option<i128> {
+    let n: i128 = token.parse().ok()?;
+    let in_range = range.is_none_or(|(min, max)| (min..=max).contains(&n));
+    (n.to_string() == token && in_range).then_some(n)
+}
+
+struct Matcher<'a> {
+    lines: Vec<&'a str>,
+    line: usize,
+    vars: HashMap<String, i128>,
+    values: Vec<(usize, &'a str)>,
+}
+
+impl<'a> Matcher<'a> {
+    fn items(&mut self, items: &[Item]) -> Option<()> {
+        for item in items {
+            match item {
+                Item::Line(fields) => {
+                    let line = *self.lines.get(self.line)?;
+                    self.line += 1;
+                    let mut tokens = line.split(' ').filter(|_| !line.is_empty());
+                    for field in fields {
+                        let n = field
+                            .repeat
+                            .as_ref()
+                            .map_or(Some(1), |count| resolve(count, &self.vars))?;
+                        for _ in 0..n {
+                            let token = tokens.next()?;
+                            if field.kind == Kind::Int {
+                                let n = parse_int(token, field.range)?;
+                                if let Some(name) = &field.name {
+                                    self.vars.insert(name.clone(), n);
+                                }
+                            }
+                            self.values.push((field.slot, token));
+                        }
+                    }
+                    if tokens.next().is_some() {
+                        return None;
+                    }
+                }
+                Item::Repeat(count, items) => {
+                    // Each repetition takes at least a line
+                    let n = resolve(count, &self.vars)?;
+                    if n > self.lines.len() - self.line {
+                        return None;
+                    }
+                    for _ in 0..n {
+                        self.items(items)?;
+                    }
+                }
+            }
+        }
+        Some(())
+    }
+}
+
+struct Generator<'r, 'a, F> {
+    reader: &'r mut BitReader<'a>,
+    next_value: F,
+    vars: HashMap<String, i128>,
+    lines: Vec<String>,
+}
+
+impl<F: FnMut(usize, &mut BitReader) -> Result<Value, DecodeError>> Generator<'_, '_, F> {
+    // Counts come from decoded values, so they are reserved like the lengths of lists
+    fn count(&mut self, count: &Count) -> Result<usize, DecodeError> {
+        let n = resolve(count, &self.vars)
+            .ok_or(DecodeError::InvalidData("schema count is out of range"))?;
+        self.reader.reserve_items(n)?;
+        Ok(n)
+    }
+
+    fn items(&mut self, items: &[Item]) -> Result<(), DecodeError> {
+        for item in items {
+            match item {
+                Item::Line(fields) => {
+                    let mut tokens = Vec::new();
+                    for field in fields {
+                        let n = match &field.repeat {
+                            Some(count) => self.count(count)?,
+                            None => 1,
+                        };
+                        for _ in 0..n {
+                            let value = (self.next_value)(field.slot, self.reader)?;
+                            tokens.push(match field.kind {
+                                Kind::Int => {
+                                    let n = value.into_int()?;
+                                    if let Some(name) = &field.name {
+                                        self.vars.insert(name.clone(), n);
+                                    }
+                                    n.to_string()
+                                }
+                                Kind::Str => value.into_string()?,
+                            });
+                        }
+                    }
+                    self.lines.push(tokens.join(" "));
+                }
+                Item::Repeat(count, items) => {
+                    for _ in 0..self.count(count)? {
+                        self.items(items)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// Slot kinds in slot order
+fn slot_kinds(items: &[Item], kinds: &mut Vec<Kind>) {
+    for item in items {
+        match item {
+            Item::Line(fields) => kinds.extend(fields.iter().map(|field| field.kind)),
+            Item::Repeat(_, items) => slot_kinds(items, kinds),
+        }
+    }
+}
+
+// Only the values of fields are compressed, with coders chosen by autocompress. Returns None if
+// some text doesn't follow the schema
+pub(crate) fn schema_compressed(
+    texts: &[&String],
+    schema: &Schema,
+    opts: AutoCompressOpts,
+) -> Option<MultiCompressedData> {
+    let files: Vec<FileValues> = texts
+        .iter()
+        .map(|text| schema.split(text))
+        .collect::<Option<_>>()?;
+
+    let mut kinds = Vec::new();
+    slot_kinds(&schema.items, &mut kinds);
+    let slots: Vec<usize> = (0..schema.n_slots).collect();
+    let fields = map(&slots, |slot| {
+        let tokens: Vec<&str> = files
+            .iter()
+            .flat_map(|file| &file.values)
+            .filter(|(value_slot, _)| value_slot == slot)
+            .map(|(_, token)| *token)
+            .collect();
+        match kinds[*slot] {
+            Kind::Int => {
+                let nums: Vec<i128> = tokens.iter().map(|token| token.parse().unwrap()).collect();
+                let nums_refs: Vec<&i128> = nums.iter().collect();
+                autocompress(&nums_refs, opts.subproblem())
+            }
+            Kind::Str => {
+                let strings: Vec<String> = tokens.iter().map(|token| token.to_string()).collect();
+                let strings_refs: Vec<&String> = strings.iter().collect();
+                autocompress(&strings_refs, opts.subproblem())
+            }
+        }
+    });
+
+    let newlines: Vec<usize> = files.iter().map(|file| file.newline as usize).collect();
+    let newlines_refs: Vec<&usize> = newlines.iter().collect();
+    let newline = autocompress(&newlines_refs, opts.subproblem());
+
+    // Values are interleaved in the order the decoder reads them
+    let mut next_value = vec![0; schema.n_slots];
+    let binary_data = files
+        .iter()
+        .zip(newline.binary_data)
+        .map(|(file, mut bits)| {
+            for (slot, _) in &file.values {
+                bits.extend(&fields[*slot].binary_data[next_value[*slot]]);
+                next_value[*slot] += 1;
+            }
+            bits
+        })
+        .collect();
+
+    Some(MultiCompressedData {
+        engine: Engine::Schema {
+            schema: schema.clone(),
+            newline: Box::new(newline.engine),
+            fields: fields.into_iter().map(|field| field.engine).collect(),
+        },
+        binary_data,
+    })
+}
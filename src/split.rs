@@ -1,17 +1,22 @@
+use crate::autocompress::AutoCompressOpts;
 use crate::compress::Compress;
 use std::collections::HashMap;
 use std::hash::Hash;
 
 pub fn try_split_by<'a, T: Compress, K: Hash + Eq>(
     objs: &[&'a T],
+    opts: AutoCompressOpts,
     key_fn: impl Fn(&'a T) -> K,
 ) -> Option<Vec<Vec<usize>>> {
     let mut indices_by_value: HashMap<K, Vec<usize>> = HashMap::new();
     for (i, x) in objs.iter().enumerate() {
         indices_by_value.entry(key_fn(x)).or_default().push(i);
     }
-    if indices_by_value.len() < objs.len() / 2 {
-        Some(indices_by_value.into_iter().map(|(_, v)| v).collect())
+    if opts.split_pays_off(indices_by_value.len(), objs.len()) {
+        // Categories are ordered by first occurrence to keep the output deterministic
+        let mut categories: Vec<Vec<usize>> = indices_by_value.into_values().collect();
+        categories.sort_by_key(|category| category[0]);
+        Some(categories)
     } else {
         None
     }
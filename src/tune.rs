@@ -0,0 +1,90 @@
+use crate::autocompress::AutoCompressOpts;
+use crate::packet::{pack, Packet};
+
+// A threshold of AutoCompressOpts and the values tried for it
+struct Param {
+    field: for<'a> fn(&'a mut AutoCompressOpts<'_>) -> &'a mut usize,
+    candidates: &'static [usize],
+}
+
+const PARAMS: [Param; 5] = [
+    Param {
+        field: |opts| &mut opts.dedup_percent,
+        candidates: &[25, 33, 50, 67, 75, 90, 100],
+    },
+    Param {
+        field: |opts| &mut opts.dedup_slack,
+        candidates: &[0, 1, 2, 3, 5, 8],
+    },
+    Param {
+        field: |opts| &mut opts.split_percent,
+        candidates: &[10, 25, 33, 50, 67, 75],
+    },
+    Param {
+        field: |opts| &mut opts.rle_percent,
+        candidates: &[25, 33, 50, 67, 75],
+    },
+    Param {
+        field: |opts| &mut opts.separator_percent,
+        candidates: &[50, 75, 100, 150, 200],
+    },
+];
+
+// Each round tries every value of every threshold, so a few rounds are enough for the search to
+// settle
+const MAX_ROUNDS: usize = 3;
+
+/// Result of [`tune`].
+pub struct Tuning<'a> {
+    /// The options with the best thresholds found.
+    pub opts: AutoCompressOpts<'a>,
+    /// Total size of the packets of the corpus with the original options, in bytes.
+    pub initial_size: usize,
+    /// Total size of the packets of the corpus with the tuned options, in bytes.
+    pub size: usize,
+}
+
+/// Searches the thresholds of [`AutoCompressOpts`] for the values that minimize the total size of
+/// the packets of a corpus, starting from `opts`.
+///
+/// Thresholds are changed one at a time, as long as some change makes the corpus smaller, so the
+/// corpus is compressed many times over. The other fields of `opts` are kept; a deadline makes
+/// sizes depend on timing and should not be set.
+pub fn tune<'a>(corpus: &[Packet], opts: AutoCompressOpts<'a>) -> Tuning<'a> {
+    let total_size = |opts| {
+        corpus
+            .iter()
+            .map(|packet| pack(packet, opts).len())
+            .sum::<usize>()
+    };
+    let initial_size = total_size(opts);
+    let mut best = opts;
+    let mut best_size = initial_size;
+    for _ in 0..MAX_ROUNDS {
+        let mut improved = false;
+        for param in &PARAMS {
+            for value in param.candidates {
+                let mut candidate = best;
+                let field = (param.field)(&mut candidate);
+                if *field == *value {
+                    continue;
+                }
+                *field = *value;
+                let size = total_size(candidate);
+                if size < best_size {
+                    best = candidate;
+                    best_size = size;
+                    improved = true;
+                }
+            }
+        }
+        if !improved {
+            break;
+        }
+    }
+    Tuning {
+        opts: best,
+        initial_size,
+        size: best_size,
+    }
+}
@@ -1,4 +1,5 @@
-use crate::bits::Bits;
+use crate::bits::{BitReader, Bits};
+use crate::decompress::DecodeError;
 
 pub fn compress_varuint(num: u128) -> Bits {
     let mut bits = Bits::new();
@@ -15,18 +16,10 @@ pub fn compress_varuint(num: u128) -> Bits {
         bits.push(true);
         bits.push(true);
 
-        let mut tmp = num;
-        let mut n_bits = 0;
-        while tmp > 0 {
-            tmp /= 2;
-            n_bits += 1;
-        }
-        n_bits -= 2;
-
-        bits.extend(&compress_varuint(n_bits));
-        for i in 0..n_bits {
-            bits.push(((num >> i) & 1) != 0);
-        }
+        let n_bits = get_bit_length(num);
+        bits.extend(&compress_varuint((n_bits - 2) as u128));
+        // The most significant bit is always set, so there's no need to store it
+        bits.push_int(num, n_bits - 1);
     }
 
     bits
@@ -44,20 +37,45 @@ pub fn compress_varint(num: i128) -> Bits {
     bits
 }
 
-pub fn compress_fixint(mut num: u128, bit_length: usize) -> Bits {
+pub fn compress_fixint(num: u128, bit_length: usize) -> Bits {
     let mut bits = Bits::new();
-    for _ in 0..bit_length {
-        bits.push((num % 2) != 0);
-        num /= 2;
-    }
+    bits.push_int(num, bit_length);
     bits
 }
 
-pub fn get_bit_length(mut num: u128) -> usize {
-    let mut bit_length = 0;
-    while num > 0 {
-        num /= 2;
-        bit_length += 1;
+pub fn get_bit_length(num: u128) -> usize {
+    (128 - num.leading_zeros()) as usize
+}
+
+pub fn decompress_varuint(reader: &mut BitReader) -> Result<u128, DecodeError> {
+    match (reader.read()?, reader.read()?) {
+        (false, false) => Ok(0),
+        (true, false) => Ok(1),
+        (false, true) => Ok(2),
+        (true, true) => {
+            let n_bits = decompress_varuint(reader)?.saturating_add(2);
+            if n_bits > 128 {
+                return Err(DecodeError::InvalidData("varint is too long"));
+            }
+            let n_bits = n_bits as usize;
+            Ok((1 << (n_bits - 1)) | decompress_fixint(reader, n_bits - 1)?)
+        }
+    }
+}
+
+pub fn decompress_varint(reader: &mut BitReader) -> Result<i128, DecodeError> {
+    let negative = reader.read()?;
+    let num = decompress_varuint(reader)?;
+    if num > i128::MAX as u128 {
+        return Err(DecodeError::InvalidData("varint is out of range"));
     }
-    bit_length
+    if negative {
+        Ok(-(num as i128) - 1)
+    } else {
+        Ok(num as i128)
+    }
+}
+
+pub fn decompress_fixint(reader: &mut BitReader, bit_length: usize) -> Result<u128, DecodeError> {
+    reader.read_int(bit_length)
 }
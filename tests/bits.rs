@@ -0,0 +1,85 @@
+use taco::{BitReader, Bits, DecodeError};
+
+// Integers of every width from 0 to 128 bits, so that most of them cross a word boundary
+fn ints() -> Vec<(u128, usize)> {
+    let mut state = 0x1234_5678_9abc_def0_u128;
+    (0..=128)
+        .map(|bit_length| {
+            state = state.wrapping_mul(0x2545_f491_4f6c_dd1d).wrapping_add(1);
+            let mask = if bit_length == 128 {
+                u128::MAX
+            } else {
+                (1 << bit_length) - 1
+            };
+            (state & mask, bit_length)
+        })
+        .collect()
+}
+
+// The same bits, one at a time
+fn to_bools(bits: &Bits) -> Vec<bool> {
+    (0..bits.len()).map(|i| bits.get(i)).collect()
+}
+
+#[test]
+fn ints_round_trip_across_word_boundaries() {
+    let mut bits = Bits::new();
+    for (num, bit_length) in ints() {
+        bits.push_int(num, bit_length);
+    }
+    assert_eq!(bits.len(), (0..=128).sum::<usize>());
+
+    let mut reader = BitReader::new(&bits);
+    for (num, bit_length) in ints() {
+        assert_eq!(
+            reader.read_int(bit_length).unwrap(),
+            num,
+            "{bit_length} bits"
+        );
+    }
+    assert_eq!(reader.remaining(), 0);
+    assert!(matches!(reader.read(), Err(DecodeError::UnexpectedEnd)));
+}
+
+#[test]
+fn unaligned_copies_keep_every_bit() {
+    let mut source = Bits::new();
+    for (num, bit_length) in ints() {
+        source.push_int(num, bit_length);
+    }
+    let expected = to_bools(&source);
+
+    for offset in [1, 7, 63, 64, 65] {
+        let mut bits = Bits::new();
+        for _ in 0..offset {
+            bits.push(true);
+        }
+        bits.extend(&source);
+        assert_eq!(to_bools(&bits)[offset..], expected, "offset {offset}");
+        assert_eq!(
+            to_bools(&bits.slice(offset..bits.len())),
+            expected,
+            "offset {offset}"
+        );
+    }
+
+    // Bytes are padded to a whole byte, which adds zeroes at the end
+    let restored = Bits::from_bytes(&source.to_bytes());
+    assert_eq!(to_bools(&restored.slice(0..source.len())), expected);
+    assert!(to_bools(&restored)[source.len()..].iter().all(|bit| !bit));
+}
+
+#[test]
+fn pops_across_word_boundaries() {
+    let mut bits = Bits::new();
+    bits.push_int(u128::MAX, 128);
+    bits.push(false);
+    assert_eq!(bits.pop(), Some(false));
+    for _ in 0..128 {
+        assert_eq!(bits.pop(), Some(true));
+    }
+    assert_eq!(bits.pop(), None);
+    // Popped bits are cleared, so pushing after a pop doesn't see them
+    bits.push_int(0, 70);
+    assert!(to_bools(&bits).iter().all(|bit| !bit));
+}
@@ -0,0 +1,200 @@
+use std::path::{Path, PathBuf};
+use std::process::{Command, Output};
+
+use taco::{pack, pack_with_schema, packet_to_cpp, AutoCompressOpts, FileContents, Packet, Schema};
+
+fn text(seed: u32) -> FileContents {
+    let lines: Vec<String> = (0..100)
+        .map(|i| format!("{} {} x{}", i * seed % 97, i * i * seed, i % 3))
+        .collect();
+    FileContents::Text(lines.join("\n") + "\n")
+}
+
+// Sorted sets of bytes, which are stored as sets
+fn byte_set(seed: u32) -> FileContents {
+    FileContents::Binary((0..=255).filter(|i| u32::from(*i) % seed != 1).collect())
+}
+
+// Bytes that take three values equally often, which are range coded
+fn uniform_bytes(seed: u32) -> FileContents {
+    let mut state = seed;
+    let bytes = (0..1000)
+        .map(|_| {
+            state = state.wrapping_mul(1_103_515_245).wrapping_add(12_345);
+            (state >> 16) as u8 % 3 * 30
+        })
+        .collect();
+    FileContents::Binary(bytes)
+}
+
+// Decimals and words made of a few letters, which are context modeled
+fn decimals_and_words(seed: u32) -> FileContents {
+    let lines: Vec<String> = (0..40)
+        .map(|i| {
+            let word: String = (0..i % 7 + 2)
+                .map(|j| ['t', 'a', 'c', 'o'][((i * seed + j * j) % 4) as usize])
+                .collect();
+            format!("{}.{:02} {word}", i * seed % 50, i * 7 % 100)
+        })
+        .collect();
+    FileContents::Text(lines.join("\n") + "\n")
+}
+
+fn unnamed(files: Vec<FileContents>) -> Packet {
+    Packet {
+        names: None,
+        files,
+        answers: None,
+    }
+}
+
+fn packets() -> Vec<Packet> {
+    vec![
+        Packet {
+            names: Some(vec!["a.txt".into(), "b.bin".into(), "c.txt".into()]),
+            files: vec![
+                text(3),
+                FileContents::Binary([0xff, 0x00, 0x80, 0xfe].repeat(100)),
+                text(5),
+            ],
+            answers: None,
+        },
+        unnamed(
+            (1..=6)
+                .map(|seed| match seed % 3 {
+                    0 => FileContents::Text(String::new()),
+                    1 => text(seed),
+                    _ => {
+                        FileContents::Text(format!("{seed}\nhello, world\n").repeat(seed as usize))
+                    }
+                })
+                .collect(),
+        ),
+        unnamed((2..10).map(byte_set).collect()),
+        unnamed((1..=4).map(uniform_bytes).collect()),
+        unnamed((1..=8).map(decimals_and_words).collect()),
+    ]
+}
+
+fn file_bytes(file: &FileContents) -> &[u8] {
+    match file {
+        FileContents::Text(text) => text.as_bytes(),
+        FileContents::Binary(bytes) => bytes,
+    }
+}
+
+// The program, compiled in its own directory, or None if there is no C++ compiler
+fn compile(source: &str, name: &str) -> Option<PathBuf> {
+    let name = name.replace(|c: char| !c.is_ascii_alphanumeric(), "-");
+    let dir = std::env::temp_dir().join(format!("taco-cpp-{}-{name}", std::process::id()));
+    std::fs::create_dir_all(&dir).unwrap();
+    let source_path = dir.join("main.cpp");
+    let binary_path = dir.join("main");
+    std::fs::write(&source_path, source).unwrap();
+    let status = Command::new("c++")
+        .arg("-o")
+        .arg(&binary_path)
+        .arg(&source_path)
+        .status()
+        .ok()?;
+    assert!(
+        status.success(),
+        "failed to compile {}",
+        source_path.display()
+    );
+    Some(binary_path)
+}
+
+fn run(binary: &Path, args: &[&str]) -> Output {
+    Command::new(binary).args(args).output().unwrap()
+}
+
+// Compiles the program for the packet and checks that it prints every file, and each of them
+fn check_program(packet: &Packet, packed: &[u8], name: &str) {
+    let source = packet_to_cpp(packed).unwrap();
+    let Some(binary) = compile(&source, name) else {
+        eprintln!("no C++ compiler, skipping");
+        return;
+    };
+
+    let output = run(&binary, &[]);
+    assert!(output.status.success(), "{name}");
+    let all: Vec<u8> = packet.files.iter().flat_map(file_bytes).copied().collect();
+    assert_eq!(output.stdout, all, "{name}");
+
+    for (i, file) in packet.files.iter().enumerate() {
+        let output = run(&binary, &[&(i + 1).to_string()]);
+        assert!(output.status.success(), "{name}, file {i}");
+        assert_eq!(output.stdout, file_bytes(file), "{name}, file {i}");
+    }
+    let output = run(&binary, &[&(packet.files.len() + 1).to_string()]);
+    assert_eq!(output.status.code(), Some(1), "{name}");
+    let message = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        message.contains(&packet.files.len().to_string()),
+        "{message}"
+    );
+
+    std::fs::remove_dir_all(binary.parent().unwrap()).unwrap();
+}
+
+#[test]
+fn compiled_program_prints_the_files() {
+    // Level 0 only uses direct encodings, and the maximum level most of the others
+    for level in [0, AutoCompressOpts::MAX_LEVEL] {
+        for (i, packet) in packets().iter().enumerate() {
+            let opts = AutoCompressOpts {
+                level,
+                ..AutoCompressOpts::default()
+            };
+            check_program(
+                packet,
+                &pack(packet, opts),
+                &format!("level {level}, packet {i}"),
+            );
+        }
+    }
+}
+
+#[test]
+fn compiled_program_prints_files_packed_with_a_schema() {
+    let schema = Schema::parse("n / n ints").unwrap();
+    let packet = unnamed(
+        (1..=5)
+            .map(|n| {
+                let list: Vec<String> = (0..n).map(|i| (i * i * 31 % 1000).to_string()).collect();
+                FileContents::Text(format!("{n}\n{}\n", list.join(" ")))
+            })
+            .collect(),
+    );
+    let packed = pack_with_schema(&packet, &schema, AutoCompressOpts::default());
+    assert!(packet_to_cpp(&packed).unwrap().contains("Schema::read(r)"));
+    check_program(&packet, &packed, "schema");
+}
+
+#[test]
+fn only_the_used_decoders_are_included() {
+    let packet = unnamed(vec![FileContents::Text("1 2 3\n".into()); 2]);
+    let opts = AutoCompressOpts {
+        level: 0,
+        ..AutoCompressOpts::default()
+    };
+    let source = packet_to_cpp(&pack(&packet, opts)).unwrap();
+    assert!(source.contains("int main(int argc, char** argv)"));
+    assert!(source.contains("static const unsigned char PACKET[] = {\n"));
+    let cases: Vec<&str> = source
+        .lines()
+        .filter(|line| line.starts_with("    case "))
+        .collect();
+    assert_eq!(
+        cases,
+        [
+            "    case 1: return FixedInt::read(r);",
+            "    case 4: return String::read(r);",
+            "    case 8: return Vec::read(r);",
+        ]
+    );
+    for name in ["Huffman", "RangeDecoder", "Queue"] {
+        assert!(!source.contains(name), "{name}");
+    }
+}
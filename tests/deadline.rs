@@ -0,0 +1,44 @@
+use std::time::Instant;
+use taco::{compress, decompress, pack, unpack, AutoCompressOpts, FileContents, Packet, Value};
+
+fn texts() -> Vec<String> {
+    (0..20)
+        .map(|i: u32| {
+            let tokens: Vec<String> = (0..30)
+                .map(|j| ((i * 7 + j * j) % 90).to_string())
+                .collect();
+            format!("{}\n{}\n", tokens.len(), tokens.join(" "))
+        })
+        .collect()
+}
+
+// A deadline that has already passed, so no strategy but the direct encodings is tried
+fn expired() -> AutoCompressOpts<'static> {
+    AutoCompressOpts {
+        deadline: Some(Instant::now()),
+        ..AutoCompressOpts::default()
+    }
+}
+
+#[test]
+fn expired_deadline_gives_decodable_data() {
+    let objs = texts();
+    let data = compress(&objs, expired());
+    let values = decompress(&data.to_bits().to_bytes(), objs.len()).unwrap();
+    let expected: Vec<Value> = objs.into_iter().map(Value::Str).collect();
+    assert_eq!(values, expected);
+}
+
+#[test]
+fn expired_deadline_gives_a_valid_packet() {
+    let packet = Packet {
+        names: None,
+        files: texts().into_iter().map(FileContents::Text).collect(),
+        answers: None,
+    };
+    let bytes = pack(&packet, expired());
+    assert_eq!(unpack(&bytes).unwrap(), packet);
+    // Nothing is searched, but the data is still smaller than the files
+    let total: usize = packet.files.iter().map(|file| file.as_bytes().len()).sum();
+    assert!(bytes.len() < total, "{} bytes for {total}", bytes.len());
+}
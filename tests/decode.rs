@@ -0,0 +1,178 @@
+use taco::{
+    unpack, AutoCompressOpts, BitReader, Bits, DecodeError, Decoder, Engine, FileContents, Schema,
+    Value, MAGIC, VERSION,
+};
+
+fn zeros(n: usize) -> Bits {
+    let mut bits = Bits::new();
+    for _ in 0..n {
+        bits.push(false);
+    }
+    bits
+}
+
+#[test]
+fn range_coded_ints_longer_than_data_are_rejected() {
+    let engine = Engine::RangeCodedInt {
+        bias: 0,
+        size: 1000,
+        length: usize::MAX,
+        data: zeros(64),
+    };
+    assert!(matches!(
+        Decoder::new(&engine),
+        Err(DecodeError::InvalidData(_))
+    ));
+}
+
+#[test]
+fn range_coded_symbols_longer_than_data_are_rejected() {
+    let opts = AutoCompressOpts::default();
+    let alphabet = taco::compress(&[vec![&10usize, &20]], opts);
+    let freqs = taco::compress(&[vec![&1usize, &1]], opts);
+    let engine = Engine::RangeCoded {
+        length: usize::MAX,
+        alphabet_engine: Box::new(alphabet.engine),
+        alphabet_data: alphabet.binary_data[0].clone(),
+        freqs_engine: Box::new(freqs.engine),
+        freqs_data: freqs.binary_data[0].clone(),
+        data: zeros(64),
+    };
+    assert!(matches!(
+        Decoder::new(&engine),
+        Err(DecodeError::InvalidData(_))
+    ));
+}
+
+#[test]
+fn context_strings_longer_than_data_are_rejected() {
+    let alphabet = taco::compress(&[vec![&'a', &'b']], AutoCompressOpts::default());
+    let engine = Engine::ContextString {
+        order: 1,
+        length: usize::MAX,
+        alphabet_engine: Box::new(alphabet.engine),
+        alphabet_data: alphabet.binary_data[0].clone(),
+        data: zeros(64),
+    };
+    assert!(matches!(
+        Decoder::new(&engine),
+        Err(DecodeError::InvalidData(_))
+    ));
+}
+
+#[test]
+fn range_coded_ints_of_a_single_value_are_rejected() {
+    let engine = Engine::RangeCodedInt {
+        bias: 0,
+        size: 1,
+        length: 1 << 33,
+        data: zeros(64),
+    };
+    assert!(matches!(
+        Decoder::new(&engine),
+        Err(DecodeError::InvalidData(_))
+    ));
+}
+
+#[test]
+fn range_coded_symbols_of_a_single_symbol_are_rejected() {
+    let opts = AutoCompressOpts::default();
+    let alphabet = taco::compress(&[vec![&10usize]], opts);
+    let freqs = taco::compress(&[vec![&1usize]], opts);
+    let engine = Engine::RangeCoded {
+        length: 1 << 33,
+        alphabet_engine: Box::new(alphabet.engine),
+        alphabet_data: alphabet.binary_data[0].clone(),
+        freqs_engine: Box::new(freqs.engine),
+        freqs_data: freqs.binary_data[0].clone(),
+        data: zeros(64),
+    };
+    assert!(matches!(
+        Decoder::new(&engine),
+        Err(DecodeError::InvalidData(_))
+    ));
+}
+
+// An engine that decodes to `value` without reading any bits
+fn constant(value: i128) -> Box<Engine> {
+    Box::new(Engine::FixedInt {
+        bias: value,
+        length: 0,
+    })
+}
+
+fn decode_empty(engine: &Engine) -> Result<Value, DecodeError> {
+    Decoder::new(engine)?.decode(&mut BitReader::new(&Bits::new()))
+}
+
+#[test]
+fn lists_of_zero_width_items_longer_than_data_are_rejected() {
+    let engine = Engine::Vec {
+        length: constant(1 << 40),
+        item: constant(0),
+    };
+    assert!(matches!(
+        decode_empty(&engine),
+        Err(DecodeError::InvalidData(_))
+    ));
+
+    // A single run is just as long
+    let engine = Engine::VecRLE {
+        length: Box::new(Engine::Vec {
+            length: constant(1),
+            item: constant(1 << 40),
+        }),
+        item: Box::new(Engine::Vec {
+            length: constant(1),
+            item: constant(0),
+        }),
+    };
+    assert!(matches!(
+        decode_empty(&engine),
+        Err(DecodeError::InvalidData(_))
+    ));
+}
+
+#[test]
+fn schema_counts_larger_than_data_are_rejected() {
+    let engine = Engine::Schema {
+        schema: Schema::parse("n / n ints").unwrap(),
+        newline: constant(1),
+        fields: vec![*constant(1 << 40), *constant(0)],
+    };
+    assert!(matches!(
+        decode_empty(&engine),
+        Err(DecodeError::InvalidData(_))
+    ));
+}
+
+#[test]
+fn answer_line_counts_larger_than_data_are_rejected() {
+    let opts = AutoCompressOpts::default();
+    let input = FileContents::Text("1000000000000\n".into());
+    let empty_line = taco::compress(&[String::new()], opts);
+
+    // One input, no names, with an answer whose lines take no bits
+    let mut payload = Bits::new();
+    for bit in [true, false, false, true] {
+        payload.push(bit);
+    }
+    payload.extend(&taco::compress(&[input], opts).to_bits());
+    payload.push(true);
+    Engine::AnswerLines {
+        token: 0,
+        offset: 0,
+        newline: constant(1),
+        line: Box::new(Engine::Constant {
+            engine: Box::new(empty_line.engine),
+            data: empty_line.binary_data[0].clone(),
+        }),
+    }
+    .push_to_bits(&mut payload);
+
+    let mut bytes = MAGIC.to_vec();
+    bytes.push(VERSION);
+    bytes.extend_from_slice(&(payload.len() as u64).to_le_bytes());
+    bytes.extend_from_slice(&payload.to_bytes());
+    assert!(matches!(unpack(&bytes), Err(DecodeError::InvalidData(_))));
+}
@@ -0,0 +1,38 @@
+use taco::{compress, AutoCompressOpts};
+
+// Lines with many tokens of equal frequency, so that Huffman codes have to break ties
+fn lines() -> Vec<String> {
+    (0..200)
+        .map(|i: u32| {
+            let tokens: Vec<String> = (0..8)
+                .map(|j| ((i * 31 + j * 17) % 40).to_string())
+                .collect();
+            tokens.join(" ") + "\n"
+        })
+        .collect()
+}
+
+fn compressed(objs: &[String]) -> Vec<u8> {
+    compress(objs, AutoCompressOpts::default())
+        .to_bits()
+        .to_bytes()
+}
+
+#[test]
+fn compressing_twice_gives_the_same_bytes() {
+    let objs = lines();
+    let expected = compressed(&objs);
+    assert_eq!(compressed(&objs), expected);
+
+    // Compressions running at the same time share threads, but not results
+    let results: Vec<Vec<u8>> = std::thread::scope(|scope| {
+        let handles: Vec<_> = (0..4).map(|_| scope.spawn(|| compressed(&objs))).collect();
+        handles
+            .into_iter()
+            .map(|handle| handle.join().unwrap())
+            .collect()
+    });
+    for result in results {
+        assert_eq!(result, expected);
+    }
+}
@@ -0,0 +1,52 @@
+use taco::{compress, decompress, AutoCompressOpts, Compress, Value};
+
+// Compresses the objects with an engine tree that must contain `engine`, and restores them
+fn round_trip<T: Compress>(objs: &[T], opts: AutoCompressOpts, engine: &str) -> Vec<Value> {
+    let data = compress(objs, opts);
+    let tree = format!("{:?}", data.engine);
+    assert!(tree.contains(engine), "{engine} not in {tree}");
+    decompress(&data.to_bits().to_bytes(), objs.len()).unwrap()
+}
+
+#[test]
+fn canonical_huffman_codes_of_different_lengths_round_trip() {
+    // Frequencies halve from one word to the next, so that every code has a different length
+    let words = ["a", "bb", "ccc", "dddd", "eeeee", "ffffff"];
+    let objs: Vec<String> = (0..256)
+        .map(|i: u32| words[(i + 1).trailing_zeros().min(5) as usize].to_string())
+        .collect();
+    let opts = AutoCompressOpts {
+        level: 1,
+        ..AutoCompressOpts::default()
+    };
+    let values = round_trip(&objs, opts, "CanonicalHuffman");
+    let expected: Vec<Value> = objs.into_iter().map(Value::Str).collect();
+    assert_eq!(values, expected);
+}
+
+#[test]
+fn int_sets_of_different_lengths_round_trip() {
+    // Sorted sets are only tried for bytes
+    let sets: Vec<Vec<u8>> = (2..10)
+        .map(|n| (0..=255).filter(|i| i % n != 1).collect())
+        .collect();
+    let objs: Vec<Vec<&u8>> = sets.iter().map(|set| set.iter().collect()).collect();
+    let values = round_trip(&objs, AutoCompressOpts::default(), "IntSet");
+    let expected: Vec<Value> = sets
+        .iter()
+        .map(|set| Value::Vec(set.iter().map(|x| Value::Int(*x as i128)).collect()))
+        .collect();
+    assert_eq!(values, expected);
+}
+
+#[test]
+fn large_int_biases_round_trip() {
+    // The bias of fixed-width integers is stored as a varint, with its most significant bit at
+    // every position in turn
+    for bit in 0..120 {
+        let objs: Vec<i128> = (0..10).map(|i| (1 << bit) + i).collect();
+        let values = round_trip(&objs, AutoCompressOpts::default(), "FixedInt");
+        let expected: Vec<Value> = objs.into_iter().map(Value::Int).collect();
+        assert_eq!(values, expected);
+    }
+}
@@ -0,0 +1,32 @@
+use taco::{explain, AutoCompressOpts};
+
+fn lines(seed: u32) -> Vec<String> {
+    (0..50)
+        .map(|i| format!("{} {}\n", i * seed % 97, i % 3))
+        .collect()
+}
+
+#[test]
+fn concurrent_explanations_are_independent() {
+    let opts = AutoCompressOpts::default();
+    let groups = [lines(7), lines(13)];
+    let expected: Vec<String> = groups
+        .iter()
+        .map(|objs| explain(objs, opts).unwrap())
+        .collect();
+    assert!(expected.iter().all(|report| report.contains("rejected")));
+
+    for _ in 0..4 {
+        let reports: Vec<String> = std::thread::scope(|scope| {
+            let handles: Vec<_> = groups
+                .iter()
+                .map(|objs| scope.spawn(move || explain(objs, opts).unwrap()))
+                .collect();
+            handles
+                .into_iter()
+                .map(|handle| handle.join().unwrap())
+                .collect()
+        });
+        assert_eq!(reports, expected);
+    }
+}
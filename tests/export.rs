@@ -0,0 +1,204 @@
+use taco::{compress, AutoCompressOpts};
+
+// Just enough JSON to read the export back
+#[derive(Debug)]
+enum Json {
+    Bool(bool),
+    Number(String),
+    Str(String),
+    Array(Vec<Json>),
+    Object(Vec<(String, Json)>),
+}
+
+struct Parser<'a> {
+    chars: std::iter::Peekable<std::str::Chars<'a>>,
+}
+
+impl Parser<'_> {
+    fn expect(&mut self, expected: char) {
+        assert_eq!(self.chars.next(), Some(expected));
+    }
+
+    fn value(&mut self) -> Json {
+        match *self.chars.peek().unwrap() {
+            '{' => {
+                self.expect('{');
+                let mut fields = Vec::new();
+                while self.chars.peek() != Some(&'}') {
+                    if !fields.is_empty() {
+                        self.expect(',');
+                    }
+                    let key = self.string();
+                    self.expect(':');
+                    fields.push((key, self.value()));
+                }
+                self.expect('}');
+                Json::Object(fields)
+            }
+            '[' => {
+                self.expect('[');
+                let mut items = Vec::new();
+                while self.chars.peek() != Some(&']') {
+                    if !items.is_empty() {
+                        self.expect(',');
+                    }
+                    items.push(self.value());
+                }
+                self.expect(']');
+                Json::Array(items)
+            }
+            '"' => Json::Str(self.string()),
+            't' | 'f' => {
+                let word: String =
+                    std::iter::from_fn(|| self.chars.next_if(|c| c.is_ascii_alphabetic()))
+                        .collect();
+                Json::Bool(word.parse().unwrap())
+            }
+            _ => Json::Number(
+                std::iter::from_fn(|| self.chars.next_if(|c| *c == '-' || c.is_ascii_digit()))
+                    .collect(),
+            ),
+        }
+    }
+
+    fn string(&mut self) -> String {
+        self.expect('"');
+        let mut s = String::new();
+        loop {
+            match self.chars.next().unwrap() {
+                '"' => return s,
+                '\\' => match self.chars.next().unwrap() {
+                    'n' => s.push('\n'),
+                    'r' => s.push('\r'),
+                    't' => s.push('\t'),
+                    'u' => {
+                        let code: String = (0..4).map(|_| self.chars.next().unwrap()).collect();
+                        s.push(char::from_u32(u32::from_str_radix(&code, 16).unwrap()).unwrap());
+                    }
+                    c => s.push(c),
+                },
+                c => s.push(c),
+            }
+        }
+    }
+}
+
+fn parse(text: &str) -> Json {
+    let mut parser = Parser {
+        chars: text.chars().peekable(),
+    };
+    let json = parser.value();
+    assert_eq!(parser.chars.next(), None);
+    json
+}
+
+// Writes the value back the way the export does, without spaces
+fn write(json: &Json, out: &mut String) {
+    match json {
+        Json::Bool(value) => out.push_str(&value.to_string()),
+        Json::Number(value) => out.push_str(value),
+        Json::Str(value) => write_string(value, out),
+        Json::Array(items) => {
+            out.push('[');
+            for (i, item) in items.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write(item, out);
+            }
+            out.push(']');
+        }
+        Json::Object(fields) => {
+            out.push('{');
+            for (i, (key, value)) in fields.iter().enumerate() {
+                if i > 0 {
+                    out.push(',');
+                }
+                write_string(key, out);
+                out.push(':');
+                write(value, out);
+            }
+            out.push('}');
+        }
+    }
+}
+
+fn write_string(s: &str, out: &mut String) {
+    out.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => out.push_str("\\\""),
+            '\\' => out.push_str("\\\\"),
+            '\n' => out.push_str("\\n"),
+            '\r' => out.push_str("\\r"),
+            '\t' => out.push_str("\\t"),
+            c if (c as u32) < 0x20 => out.push_str(&format!("\\u{:04x}", c as u32)),
+            c => out.push(c),
+        }
+    }
+    out.push('"');
+}
+
+// Number of engine nodes, each of which has a type
+fn count_nodes(json: &Json) -> usize {
+    match json {
+        Json::Array(items) => items.iter().map(count_nodes).sum(),
+        Json::Object(fields) => {
+            let own = fields.iter().any(|(key, _)| key == "type") as usize;
+            own + fields
+                .iter()
+                .map(|(_, value)| count_nodes(value))
+                .sum::<usize>()
+        }
+        _ => 0,
+    }
+}
+
+// Lines of tokens, so that the tree has separators that need escaping
+fn lines() -> Vec<String> {
+    (0..30)
+        .map(|i: u32| {
+            format!(
+                "{} {}\n{}\n",
+                i % 7,
+                i * 13 % 50,
+                "ab\"c\\".repeat(i as usize % 3)
+            )
+        })
+        .collect()
+}
+
+#[test]
+fn json_export_reads_back_to_the_same_tree() {
+    let engine = compress(&lines(), AutoCompressOpts::default()).engine;
+    let json = engine.to_json();
+    assert!(json.contains("\"separator\":\"\\n\""), "{json}");
+    let parsed = parse(&json);
+    let mut written = String::new();
+    write(&parsed, &mut written);
+    assert_eq!(written, json);
+
+    let Json::Object(fields) = &parsed else {
+        panic!("expected an object, got {parsed:?}");
+    };
+    let Some((_, Json::Str(name))) = fields.first() else {
+        panic!("expected a type first, got {fields:?}");
+    };
+    assert!(format!("{engine:?}").starts_with(name.as_str()), "{name}");
+}
+
+#[test]
+fn dot_export_has_a_node_per_engine() {
+    let engine = compress(&lines(), AutoCompressOpts::default()).engine;
+    let n_nodes = count_nodes(&parse(&engine.to_json()));
+    let dot = engine.to_dot();
+    assert!(dot.starts_with("digraph engine {\n"));
+    assert!(dot.ends_with("}\n"));
+    let nodes = dot
+        .lines()
+        .filter(|line| line.contains(" [label=") && !line.contains("->"));
+    let edges = dot.lines().filter(|line| line.contains("->"));
+    assert_eq!(nodes.count(), n_nodes);
+    // A tree, so every node but the root has an edge from its parent
+    assert_eq!(edges.count(), n_nodes - 1);
+}
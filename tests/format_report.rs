@@ -0,0 +1,40 @@
+use taco::{describe_format, AutoCompressOpts, FileContents};
+
+fn files(texts: &[String]) -> Vec<FileContents> {
+    texts
+        .iter()
+        .map(|text| FileContents::Text(text.clone()))
+        .collect()
+}
+
+// A line with two integers and a line with one
+fn test_file(i: usize) -> String {
+    format!("{} {}\n{}\n", i * 7 % 50 + 1, i * 11 % 40 + 1, i * 13 % 100)
+}
+
+#[test]
+fn lines_of_tokens_are_described_without_the_final_line_break() {
+    let texts: Vec<String> = (0..10).map(test_file).collect();
+    assert_eq!(
+        describe_format(&files(&texts), AutoCompressOpts::default()),
+        "\
+Each object: 2 lines:
+  line 1: 2 tokens:
+    token 1: integer in [1, 50]
+    token 2: integer in [1, 38]
+  line 2: integer in [0, 91]
+"
+    );
+}
+
+#[test]
+fn files_without_the_final_line_break_are_counted() {
+    let mut texts: Vec<String> = (0..10).map(test_file).collect();
+    texts[3].pop();
+    let report = describe_format(&files(&texts), AutoCompressOpts::default());
+    assert!(report.starts_with("Each object: 2 lines:\n"), "{report}");
+    assert!(
+        report.ends_with("Line break at the end: 9 of 10 objects\n"),
+        "{report}"
+    );
+}
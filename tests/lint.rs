@@ -0,0 +1,52 @@
+use taco::{lint, AutoCompressOpts, FileContents};
+
+// A count and a list of that many integers, so that every line is different
+fn texts() -> Vec<String> {
+    (0..12)
+        .map(|i: usize| {
+            let list: Vec<String> = (0..i % 5 + 3)
+                .map(|j| ((i * 7919 + j * 104729) % 1_000_000_007).to_string())
+                .collect();
+            format!("{}\n{}\n", list.len(), list.join(" "))
+        })
+        .collect()
+}
+
+// Objects reported, each with the messages about it
+fn reported(texts: Vec<String>) -> Vec<(usize, String)> {
+    let files: Vec<FileContents> = texts.into_iter().map(FileContents::Text).collect();
+    lint(&files, AutoCompressOpts::default())
+        .into_iter()
+        .map(|inconsistency| (inconsistency.object, inconsistency.message))
+        .collect()
+}
+
+#[test]
+fn consistent_files_are_not_reported() {
+    assert_eq!(reported(texts()), []);
+}
+
+#[test]
+fn crlf_line_endings_are_reported() {
+    let mut texts = texts();
+    texts[4] = texts[4].replace('\n', "\r\n");
+    let reported = reported(texts);
+    assert!(!reported.is_empty());
+    for (object, message) in reported {
+        assert_eq!(object, 4, "{message}");
+        assert!(message.contains("\\r"), "{message}");
+    }
+}
+
+#[test]
+fn missing_final_line_break_is_reported() {
+    let mut texts = texts();
+    texts[9].pop();
+    assert_eq!(
+        reported(texts),
+        [(
+            9,
+            "has no empty string, unlike 11 of 12 objects".to_string()
+        )]
+    );
+}
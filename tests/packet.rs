@@ -0,0 +1,170 @@
+use taco::{
+    pack, pair_tests, unpack, AutoCompressOpts, DecodeError, FileContents, Packet, MAGIC, VERSION,
+};
+
+fn text(seed: u32) -> FileContents {
+    let lines: Vec<String> = (0..100)
+        .map(|i| format!("{} {}", i * seed % 97, i))
+        .collect();
+    FileContents::Text(lines.join("\n") + "\n")
+}
+
+// Large enough that the files aren't stored raw
+fn mixed_packet() -> Packet {
+    Packet {
+        names: Some(vec!["a.txt".into(), "b.bin".into(), "c.txt".into()]),
+        files: vec![
+            text(3),
+            FileContents::Binary([0xff, 0x00, 0x80, 0xfe].repeat(100)),
+            text(5),
+        ],
+        answers: None,
+    }
+}
+
+#[test]
+fn mixed_text_and_binary_round_trip() {
+    let packet = mixed_packet();
+    let levels = (0..=2).chain([AutoCompressOpts::MAX_LEVEL]);
+    for level in levels {
+        let opts = AutoCompressOpts {
+            level,
+            ..AutoCompressOpts::default()
+        };
+        assert_eq!(
+            unpack(&pack(&packet, opts)).unwrap(),
+            packet,
+            "level {level}"
+        );
+    }
+}
+
+fn numbers(n: u32) -> String {
+    (0..n).map(|i| format!("{}\n", i * 7 % 100)).collect()
+}
+
+#[test]
+fn answers_next_to_binary_input_round_trip() {
+    let packet = Packet {
+        names: Some(vec!["01".into(), "02".into(), "99".into()]),
+        files: vec![
+            FileContents::Text(format!("100\n{}", "1 2 3 4 5\n".repeat(30))),
+            FileContents::Text(format!("80\n{}", "1 2 3 4 5\n".repeat(30))),
+            FileContents::Binary(vec![0xff; 30]),
+        ],
+        answers: Some(vec![
+            Some(FileContents::Text(numbers(100))),
+            Some(FileContents::Text(numbers(80))),
+            None,
+        ]),
+    };
+    let levels = (0..=2).chain([AutoCompressOpts::MAX_LEVEL]);
+    for level in levels {
+        let opts = AutoCompressOpts {
+            level,
+            ..AutoCompressOpts::default()
+        };
+        assert_eq!(
+            unpack(&pack(&packet, opts)).unwrap(),
+            packet,
+            "level {level}"
+        );
+    }
+}
+
+#[test]
+fn truncated_packets_are_rejected() {
+    let bytes = pack(&mixed_packet(), AutoCompressOpts::default());
+    // Inside the header, the payload and the checksums
+    for length in [
+        MAGIC.len() + 1,
+        MAGIC.len() + 9,
+        bytes.len() / 2,
+        bytes.len() - 1,
+    ] {
+        assert!(
+            matches!(unpack(&bytes[..length]), Err(DecodeError::UnexpectedEnd)),
+            "length {length}"
+        );
+    }
+}
+
+#[test]
+fn packets_with_wrong_magic_are_rejected() {
+    let mut bytes = pack(&mixed_packet(), AutoCompressOpts::default());
+    bytes[0] = b'Z';
+    assert!(matches!(unpack(&bytes), Err(DecodeError::InvalidMagic)));
+}
+
+#[test]
+fn packets_of_older_versions_are_rejected() {
+    let mut bytes = pack(&mixed_packet(), AutoCompressOpts::default());
+    bytes[MAGIC.len()] = VERSION - 1;
+    assert!(matches!(
+        unpack(&bytes),
+        Err(DecodeError::UnsupportedVersion(version)) if version == VERSION - 1
+    ));
+}
+
+#[test]
+fn packets_with_checksum_mismatch_are_rejected() {
+    let mut bytes = pack(&mixed_packet(), AutoCompressOpts::default());
+    // The last byte belongs to the checksum of the last file
+    *bytes.last_mut().unwrap() ^= 1;
+    assert!(matches!(
+        unpack(&bytes),
+        Err(DecodeError::ChecksumMismatch { file: 2 })
+    ));
+}
+
+// Files contain their names, so that it's clear where each of them ends up
+fn contents(name: &str) -> FileContents {
+    FileContents::Text(format!("{name}\n"))
+}
+
+fn named_files(names: &[&str]) -> Vec<(String, FileContents)> {
+    names
+        .iter()
+        .map(|name| (name.to_string(), contents(name)))
+        .collect()
+}
+
+#[test]
+fn tests_are_paired_with_their_answers() {
+    let packet = pair_tests(named_files(&[
+        "x.out",
+        "02.a",
+        "readme.txt",
+        "01",
+        "03.a",
+        "x.in",
+        "01.a",
+        "y.in",
+        "02",
+        "z.out",
+    ]));
+    let inputs = ["01", "02", "03.a", "readme.txt", "x.in", "y.in", "z.out"];
+    assert_eq!(packet.names.unwrap(), inputs);
+    assert_eq!(packet.files, inputs.map(contents));
+    // Answers without an input, and files named like inputs without an answer, are left alone
+    assert_eq!(
+        packet.answers.unwrap(),
+        [
+            Some(contents("01.a")),
+            Some(contents("02.a")),
+            None,
+            None,
+            Some(contents("x.out")),
+            None,
+            None,
+        ]
+    );
+}
+
+#[test]
+fn files_without_answers_have_no_answers() {
+    let packet = pair_tests(named_files(&["b.txt", "a.in", "01.out"]));
+    assert_eq!(packet.names.unwrap(), ["01.out", "a.in", "b.txt"]);
+    assert_eq!(packet.files, ["01.out", "a.in", "b.txt"].map(contents));
+    assert_eq!(packet.answers, None);
+}
@@ -0,0 +1,65 @@
+use taco::{compress, sample, AutoCompressOpts, FileContents, Value};
+
+#[test]
+fn sampled_ints_stay_within_the_compressed_range() {
+    // The range takes 11 bits, which would allow numbers up to 3047
+    let nums: Vec<u32> = (0..50).map(|i| 1000 + i * 37).collect();
+    let opts = AutoCompressOpts {
+        level: 0,
+        ..AutoCompressOpts::default()
+    };
+    let data = compress(&nums, opts);
+    assert!(format!("{:?}", data.engine).contains("FixedInt"));
+    for value in sample(&data, 1, 1000).unwrap() {
+        let Value::Int(num) = value else {
+            panic!("expected an integer, got {value:?}");
+        };
+        assert!((1000..=2813).contains(&num), "{num} is out of range");
+    }
+}
+
+fn sample_texts(files: &[FileContents], seed: u64, n: usize) -> Vec<String> {
+    let data = compress(files, AutoCompressOpts::default());
+    sample(&data, seed, n)
+        .unwrap()
+        .into_iter()
+        .map(|value| value.into_string().unwrap())
+        .collect()
+}
+
+#[test]
+fn sampled_files_keep_the_layout_of_lines() {
+    // A line with two integers and a line with one
+    let files: Vec<FileContents> = (0..10)
+        .map(|i| {
+            FileContents::Text(format!(
+                "{} {}\n{}\n",
+                i * 7 % 50 + 1,
+                i * 11 % 40 + 1,
+                i * 13 % 100
+            ))
+        })
+        .collect();
+    for text in sample_texts(&files, 1, 20) {
+        let tokens_per_line: Vec<usize> =
+            text.lines().map(|line| line.split(' ').count()).collect();
+        assert_eq!(tokens_per_line, [2, 1], "{text:?}");
+        assert!(text.ends_with('\n'), "{text:?}");
+    }
+}
+
+#[test]
+fn sampled_files_keep_the_final_line_break() {
+    // Lines of different lengths, which are compressed as strings rather than lists of tokens
+    let files: Vec<FileContents> = (0..12)
+        .map(|i| {
+            let lines: Vec<String> = (0..i % 4 + 2)
+                .map(|j| format!("{} {}", (i * 17 + j * 5) % 50, j * 31 % 7))
+                .collect();
+            FileContents::Text(lines.join("\n") + "\n")
+        })
+        .collect();
+    for text in sample_texts(&files, 2, 20) {
+        assert!(text.ends_with('\n'), "{text:?}");
+    }
+}
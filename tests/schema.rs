@@ -0,0 +1,68 @@
+use taco::{compress_with_schema, decompress, AutoCompressOpts, FileContents, Schema, Value};
+
+// Compresses the files with the schema and restores them, returning the engine tree
+fn round_trip(texts: &[&str], schema: &Schema) -> String {
+    let files: Vec<FileContents> = texts
+        .iter()
+        .map(|text| FileContents::Text(text.to_string()))
+        .collect();
+    let data = compress_with_schema(&files, schema, AutoCompressOpts::default());
+    let values = decompress(&data.to_bits().to_bytes(), files.len()).unwrap();
+    let expected: Vec<Value> = texts
+        .iter()
+        .map(|text| Value::Str(text.to_string()))
+        .collect();
+    assert_eq!(values, expected);
+    format!("{:?}", data.engine)
+}
+
+fn parse_error(text: &str) -> String {
+    Schema::parse(text).unwrap_err().to_string()
+}
+
+#[test]
+fn lists_and_pairs_are_parsed() {
+    let schema = Schema::parse("n m / n ints in [1, 1e9] / m pairs").unwrap();
+    // n, m, the list and both numbers of a pair
+    assert_eq!(schema.n_slots(), 5);
+    let engine = round_trip(
+        &[
+            "3 2\n5 1000000000 1\n1 2\n3 4\n",
+            "1 3\n7\n5 6\n7 8\n9 10\n",
+        ],
+        &schema,
+    );
+    assert!(engine.starts_with("Schema"), "{engine}");
+}
+
+#[test]
+fn nested_repeats_are_parsed() {
+    let schema = Schema::parse("k / k x (n / n ints)").unwrap();
+    assert_eq!(schema.n_slots(), 3);
+    let engine = round_trip(&["2\n3\n1 2 3\n1\n4\n", "1\n2\n5 6\n"], &schema);
+    assert!(engine.starts_with("Schema"), "{engine}");
+}
+
+#[test]
+fn unknown_counts_are_rejected() {
+    assert!(parse_error("n ints").contains("\"n\" is not a known integer"));
+}
+
+#[test]
+fn empty_lines_are_rejected() {
+    assert!(parse_error("n / / n ints").contains("empty line"));
+}
+
+#[test]
+fn unbalanced_parentheses_are_rejected() {
+    assert!(parse_error("k / k x (n / n ints").contains("unexpected end"));
+    assert!(parse_error("k / k x n / n ints)").contains("unexpected \")\""));
+}
+
+#[test]
+fn files_that_do_not_follow_the_schema_fall_back_to_compress() {
+    let schema = Schema::parse("n / n ints").unwrap();
+    // The second file has fewer integers than its count
+    let engine = round_trip(&["3\n1 2 3\n", "4\n1 2\n"], &schema);
+    assert!(!engine.contains("Schema"), "{engine}");
+}
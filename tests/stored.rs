@@ -0,0 +1,29 @@
+use taco::{pack, unpack, AutoCompressOpts, FileContents, Packet};
+
+// Xorshift, so that the bytes don't compress
+fn random_bytes(seed: u64, n: usize) -> Vec<u8> {
+    let mut state = seed;
+    (0..n)
+        .map(|_| {
+            state ^= state << 13;
+            state ^= state >> 7;
+            state ^= state << 17;
+            (state >> 56) as u8
+        })
+        .collect()
+}
+
+#[test]
+fn random_bytes_are_stored_with_bounded_overhead() {
+    for (seed, n) in [(1, 0), (2, 1), (3, 300), (4, 5000)] {
+        let packet = Packet {
+            names: None,
+            files: vec![FileContents::from_bytes(random_bytes(seed, n))],
+            answers: None,
+        };
+        let bytes = pack(&packet, AutoCompressOpts::default());
+        // The header, a checksum and a few bits of the stored engine and the length
+        assert!(bytes.len() <= n + 24, "{n} bytes -> {}", bytes.len());
+        assert_eq!(unpack(&bytes).unwrap(), packet);
+    }
+}
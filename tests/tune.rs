@@ -0,0 +1,54 @@
+use taco::{compress, pack, tune, AutoCompressOpts, FileContents, Packet};
+
+#[test]
+fn rle_threshold_decides_whether_lists_are_run_length_encoded() {
+    // 8 runs in 20 items, i.e. 40%
+    let lists: Vec<Vec<u32>> = (0..8)
+        .map(|i| (0..20).map(|j| (i * 7 + j / 3 * 5) % 11).collect())
+        .collect();
+    let objs: Vec<Vec<&u32>> = lists.iter().map(|list| list.iter().collect()).collect();
+    let engine = |rle_percent| {
+        let opts = AutoCompressOpts {
+            level: 2,
+            enable_stateful: false,
+            rle_percent,
+            ..AutoCompressOpts::default()
+        };
+        format!("{:?}", compress(&objs, opts).engine)
+    };
+    assert!(!engine(25).contains("VecRLE"));
+    assert!(engine(75).contains("VecRLE"));
+}
+
+fn test_package(seed: u32) -> Packet {
+    let files = (1..=3)
+        .map(|n| {
+            let values: Vec<String> = (0..n * 20).map(|i| ((i * seed) % 53).to_string()).collect();
+            FileContents::Text(format!("{}\n{}\n", values.len(), values.join(" ")))
+        })
+        .collect();
+    Packet {
+        names: None,
+        files,
+        answers: None,
+    }
+}
+
+#[test]
+fn tuned_options_are_no_worse_than_defaults() {
+    let corpus = [test_package(7), test_package(11)];
+    let opts = AutoCompressOpts {
+        level: 2,
+        ..AutoCompressOpts::default()
+    };
+    let total_size = |opts| {
+        corpus
+            .iter()
+            .map(|packet| pack(packet, opts).len())
+            .sum::<usize>()
+    };
+    let tuning = tune(&corpus, opts);
+    assert_eq!(tuning.initial_size, total_size(opts));
+    assert_eq!(tuning.size, total_size(tuning.opts));
+    assert!(tuning.size <= tuning.initial_size);
+}